@@ -1,4 +1,14 @@
-use aarch64_cpu::asm;
+#[path = "cpu/syndrome.rs"]
+pub mod syndrome;
+
+use crate::cpu::PrivilegeLevel;
+use aarch64_cpu::{asm, registers::*};
+use core::arch::asm;
+
+/// DAIF bit encodings for the `DAIFSet`/`DAIFClr` immediates.
+mod daif_bits {
+    pub const IRQ: u8 = 0b0010;
+}
 
 pub use asm::nop;
 
@@ -9,9 +19,93 @@ pub fn spin_for_cycles(n: usize) {
     }
 }
 
+/// Sleep until an interrupt is pending, even if it is masked.
+#[inline(always)]
+pub fn wait_for_interrupt() {
+    asm::wfi();
+}
+
 #[inline(always)]
 pub fn wait_forever() -> ! {
     loop {
         asm::wfe();
     }
 }
+
+/// Whether atomic read-modify-write operations work on the executing core.
+///
+/// They are built from exclusive load/store pairs, which need the MMU and the data caches to be
+/// enabled. Before that, a store-exclusive may never succeed on real hardware.
+#[inline(always)]
+pub fn exclusives_usable() -> bool {
+    SCTLR_EL1.matches_all(SCTLR_EL1::M::Enable + SCTLR_EL1::C::Cacheable)
+}
+
+/// The processing element's current privilege level, plus the architectural name for printing.
+pub fn current_privilege_level() -> (PrivilegeLevel, &'static str) {
+    let el = CurrentEL.read_as_enum(CurrentEL::EL);
+    match el {
+        Some(CurrentEL::EL::Value::EL2) => (PrivilegeLevel::Hypervisor, "EL2"),
+        Some(CurrentEL::EL::Value::EL1) => (PrivilegeLevel::Kernel, "EL1"),
+        Some(CurrentEL::EL::Value::EL0) => (PrivilegeLevel::User, "EL0"),
+        _ => (PrivilegeLevel::Unknown, "Unknown"),
+    }
+}
+
+/// Unmask IRQs on the executing core.
+///
+/// # Safety
+///
+/// - Changes the HW state of the executing core.
+#[inline(always)]
+pub unsafe fn local_irq_unmask() {
+    asm!(
+        "msr DAIFClr, {arg}",
+        arg = const daif_bits::IRQ,
+        options(nostack, preserves_flags)
+    );
+}
+
+/// Mask IRQs on the executing core.
+///
+/// # Safety
+///
+/// - Changes the HW state of the executing core.
+#[inline(always)]
+pub unsafe fn local_irq_mask() {
+    asm!(
+        "msr DAIFSet, {arg}",
+        arg = const daif_bits::IRQ,
+        options(nostack, preserves_flags)
+    );
+}
+
+/// Mask IRQs on the executing core and return the previous DAIF state.
+#[inline(always)]
+unsafe fn local_irq_mask_save() -> u64 {
+    let saved = DAIF.get();
+    local_irq_mask();
+
+    saved
+}
+
+/// Restore a DAIF state that was saved by `local_irq_mask_save`.
+///
+/// Not using `DAIF.set()` here, because its `asm!` is marked `nomem`, which would allow the
+/// compiler to move accesses of the protected data past the restore.
+#[inline(always)]
+unsafe fn local_irq_restore(saved: u64) {
+    asm!("msr DAIF, {arg}", arg = in(reg) saved, options(nostack, preserves_flags));
+}
+
+/// Execute `f` with IRQs masked on the executing core, restoring the previous mask afterwards.
+///
+/// Nests correctly, since an inner call restores to "masked".
+#[inline(always)]
+pub fn exec_with_irq_masked<T>(f: impl FnOnce() -> T) -> T {
+    let saved = unsafe { local_irq_mask_save() };
+    let ret = f();
+    unsafe { local_irq_restore(saved) };
+
+    ret
+}
@@ -1,11 +1,136 @@
-use core::arch::global_asm;
+use crate::bsp;
+use aarch64_cpu::registers::*;
+use core::arch::{asm, global_asm};
 
 global_asm!(
     include_str!("boot.s"),
     CONST_CORE_ID_MASK = const 0b11,
+    CONST_SPIN_TABLE_BASE = const bsp::cpu::SPIN_TABLE_BASE,
 );
 
+/// Prepares the simulated exception return from EL2 into `el1_entry_addr` at EL1.
+///
+/// # Safety
+///
+/// - Must only be called while executing in EL2.
+#[inline(always)]
+unsafe fn prepare_el2_to_el1_transition(stack_end_exclusive_addr: u64, el1_entry_addr: u64) {
+    // Let EL1 access the physical timer and counter registers.
+    CNTHCTL_EL2.write(CNTHCTL_EL2::EL1PCEN::SET + CNTHCTL_EL2::EL1PCTEN::SET);
+
+    // No offset for reading the counters.
+    CNTVOFF_EL2.set(0);
+
+    // EL1 executes in AArch64 state.
+    HCR_EL2.write(HCR_EL2::RW::EL1IsAarch64);
+
+    // Return into EL1h with all interrupts masked.
+    SPSR_EL2.write(
+        SPSR_EL2::D::Masked
+            + SPSR_EL2::A::Masked
+            + SPSR_EL2::I::Masked
+            + SPSR_EL2::F::Masked
+            + SPSR_EL2::M::EL1h,
+    );
+
+    ELR_EL2.set(el1_entry_addr);
+
+    // EL1 gets the same stack that was used so far, since nothing is left on it.
+    SP_EL1.set(stack_end_exclusive_addr);
+}
+
+/// Turn on the MMU and continue in EL1 at `virt_entry_addr`, on the stack ending at
+/// `virt_stack_end_exclusive_addr`, with `arg` in x0.
+///
+/// Runs with the MMU off, at the kernel's physical address. If the core can't get there, it writes
+/// a fixed message to the UART at its physical address and is parked.
+///
+/// # Safety
+///
+/// - Must not depend on the kernel's link address, directly or through the functions it calls.
+#[inline(always)]
+unsafe fn enter_kernel_virt(
+    phys_kernel_tables_addr: u64,
+    virt_stack_end_exclusive_addr: u64,
+    virt_entry_addr: u64,
+    arg: u64,
+) -> ! {
+    use crate::memory::mmu::interface::MMU;
+
+    let el = CurrentEL.read_as_enum(CurrentEL::EL);
+    match el {
+        // The MMU settings are for EL1, and take effect with the exception return.
+        Some(CurrentEL::EL::Value::EL2) => {
+            prepare_el2_to_el1_transition(virt_stack_end_exclusive_addr, virt_entry_addr)
+        }
+        Some(CurrentEL::EL::Value::EL1) => (),
+        _ => {
+            bsp::console::early_write(b"\r\nBoot failed: Not in EL1 or EL2, parking the core\r\n");
+            crate::cpu::wait_forever()
+        }
+    }
+
+    if crate::memory::mmu::mmu()
+        .enable_mmu_and_caching(phys_kernel_tables_addr as usize)
+        .is_err()
+    {
+        bsp::console::early_write(b"\r\nBoot failed: Enabling the MMU failed, parking the core\r\n");
+        crate::cpu::wait_forever()
+    }
+
+    match el {
+        Some(CurrentEL::EL::Value::EL2) => asm!("eret", in("x0") arg, options(noreturn)),
+
+        // Still at the physical address, which the identity map keeps valid until the jump.
+        _ => asm!(
+            "mov sp, {stack}",
+            "br {entry}",
+            stack = in(reg) virt_stack_end_exclusive_addr,
+            entry = in(reg) virt_entry_addr,
+            in("x0") arg,
+            options(noreturn)
+        ),
+    }
+}
+
+/// The Rust entry of the `kernel` binary.
+///
+/// Called from `boot.s` with the boot core stack already set up, at its physical address. Drops to
+/// EL1 if the firmware left us in EL2, and continues in `kernel_init` at its virtual address.
+#[no_mangle]
+pub unsafe extern "C" fn _start_rust(
+    phys_kernel_tables_addr: u64,
+    virt_boot_core_stack_end_exclusive_addr: u64,
+    virt_kernel_init_addr: u64,
+) -> ! {
+    enter_kernel_virt(
+        phys_kernel_tables_addr,
+        virt_boot_core_stack_end_exclusive_addr,
+        virt_kernel_init_addr,
+        0,
+    )
+}
+
+/// EL1 entry of the secondary cores, reached with the core id in x0.
+#[no_mangle]
+unsafe extern "C" fn secondary_el1_entry(core_id: u64) -> ! {
+    crate::kernel_init_secondary(core_id as usize)
+}
+
+/// The Rust entry of a secondary core.
+///
+/// Called from `boot.s` with the core's own stack already set up, at its physical address.
 #[no_mangle]
-pub unsafe fn _start_rust() -> ! {
-    crate::kernel_init()
+pub unsafe extern "C" fn _start_secondary_rust(
+    core_id: u64,
+    phys_kernel_tables_addr: u64,
+    virt_stack_end_exclusive_addr: u64,
+    virt_entry_addr: u64,
+) -> ! {
+    enter_kernel_virt(
+        phys_kernel_tables_addr,
+        virt_stack_end_exclusive_addr,
+        virt_entry_addr,
+        core_id,
+    )
 }
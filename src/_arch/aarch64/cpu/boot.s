@@ -1,16 +1,28 @@
 .section .text._start
 
+// The kernel is linked to its high alias, but runs from its load address until the MMU is on.
+
+// Load the address of a symbol relative to the PC, which is its physical address before the jump
+// to the high alias.
 .macro ADR_REL register, symbol
     adrp    \register, \symbol
     add     \register, \register, #:lo12:\symbol
 .endm
 
+// Load the absolute, link time address of a symbol, which is its kernel virtual address.
+.macro ADR_ABS register, symbol
+    movz    \register, #:abs_g3:\symbol
+    movk    \register, #:abs_g2_nc:\symbol
+    movk    \register, #:abs_g1_nc:\symbol
+    movk    \register, #:abs_g0_nc:\symbol
+.endm
+
 _start:
     mrs x0, mpidr_el1
     and x0, x0, {CONST_CORE_ID_MASK}
     ldr x1, BOOT_CORE_ID
     cmp x0, x1
-    b.ne .L_parking_loop
+    b.ne .L_secondary_spin_loop
     
     ADR_REL x0, __bss_start
     ADR_REL x1, __bss_end_exclusive
@@ -31,12 +43,54 @@ _start:
     b.eq .L_parking_loop
     str w2, [x1]
     
+    // _start_rust turns on the MMU with the tables at their physical address, and continues at
+    // the virtual addresses of kernel_init and the stack.
+    ADR_REL x0, KERNEL_TABLES
+    ADR_ABS x1, __boot_core_stack_end_exclusive
+    ADR_ABS x2, kernel_init
     b _start_rust
     
 .L_parking_loop:
     wfe
     b .L_parking_loop
 
+// Secondary cores that were not held back by the firmware poll their spin table slot here until
+// the boot core publishes an entry address. x0 holds the core id.
+.L_secondary_spin_loop:
+    mov x1, {CONST_SPIN_TABLE_BASE}
+    add x1, x1, x0, lsl #3
+1:
+    wfe
+    ldr x2, [x1]
+    cbz x2, 1b
+    br x2
+
 .size _start, . - _start
 .type _start, function
 .global _start
+
+// Entry of the secondary cores, handed to them through the spin table by the boot core.
+_start_secondary:
+    mrs x0, mpidr_el1
+    and x0, x0, {CONST_CORE_ID_MASK}
+
+    // Stack end of core n is __secondary_core_stacks_start + n * stack size. It is used at its
+    // physical address until the MMU is on.
+    ADR_REL x1, __secondary_core_stacks_start
+    ADR_REL x2, __secondary_core_stack_1_end_exclusive
+    sub x2, x2, x1
+    madd x1, x0, x2, x1
+    mov sp, x1
+
+    ADR_ABS x3, __secondary_core_stacks_start
+    madd x2, x0, x2, x3
+
+    // x0 holds the core id, x1 the physical address of the tables, x2 the virtual stack end and x3
+    // the virtual entry.
+    ADR_REL x1, KERNEL_TABLES
+    ADR_ABS x3, secondary_el1_entry
+    b _start_secondary_rust
+
+.size _start_secondary, . - _start_secondary
+.type _start_secondary, function
+.global _start_secondary
@@ -0,0 +1,41 @@
+use aarch64_cpu::{
+    asm::{self, barrier},
+    registers::*,
+};
+
+// Provided by boot.s.
+extern "Rust" {
+    static _start_secondary: core::cell::UnsafeCell<()>;
+}
+
+/// Id of the executing core, taken from the affinity level 0 of MPIDR_EL1.
+#[inline(always)]
+pub fn core_id() -> usize {
+    const CORE_MASK: u64 = 0b11;
+
+    (MPIDR_EL1.get() & CORE_MASK) as usize
+}
+
+/// Kernel virtual address the secondary cores must be released to.
+pub fn secondary_entry_addr() -> usize {
+    unsafe { _start_secondary.get() as usize }
+}
+
+/// Publish `entry` in the spin table slot at `slot`.
+///
+/// The waiting cores run with caches off, so the slot is cleaned to the point of coherency.
+///
+/// # Safety
+///
+/// - `slot` must be a spin table slot of the BSP.
+pub unsafe fn write_spin_table_entry(slot: *mut u64, entry: u64) {
+    core::ptr::write_volatile(slot, entry);
+    core::arch::asm!("dc civac, {}", in(reg) slot, options(nostack));
+}
+
+/// Make previously published spin table entries visible and wake up the cores waiting in `wfe`.
+#[inline(always)]
+pub fn wake_secondary_cores() {
+    barrier::dsb(barrier::SY);
+    asm::sev();
+}
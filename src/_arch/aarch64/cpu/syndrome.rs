@@ -0,0 +1,255 @@
+//! Decoding of the exception syndrome (ESR_EL1) and fault address (FAR_EL1) registers.
+
+use core::fmt;
+
+/// The exception class, ESR_EL1[31:26].
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum ExceptionClass {
+    Unknown,
+    TrappedWfiWfe,
+    TrappedFpSimd,
+    IllegalExecutionState,
+    Svc,
+    Hvc,
+    Smc,
+    TrappedMsrMrs,
+    InstructionAbortLowerEL,
+    InstructionAbortCurrentEL,
+    PcAlignment,
+    DataAbortLowerEL,
+    DataAbortCurrentEL,
+    SpAlignment,
+    FpException,
+    SError,
+    BreakpointLowerEL,
+    BreakpointCurrentEL,
+    SoftwareStepLowerEL,
+    SoftwareStepCurrentEL,
+    WatchpointLowerEL,
+    WatchpointCurrentEL,
+    Brk,
+    Other(u8),
+}
+
+/// The fault status code of instruction and data aborts, ISS[5:0] (IFSC/DFSC).
+///
+/// Variants that carry a `u8` hold the translation table level the fault occurred on.
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum FaultStatus {
+    AddressSize(u8),
+    Translation(u8),
+    AccessFlag(u8),
+    Permission(u8),
+    SyncExternal,
+    SyncExternalOnWalk(u8),
+    SyncParityOrEcc,
+    SyncParityOrEccOnWalk(u8),
+    Alignment,
+    TlbConflict,
+    Other(u8),
+}
+
+/// A snapshot of the syndrome registers taken on exception entry.
+#[derive(Copy, Clone)]
+pub struct Syndrome {
+    esr: u64,
+    far: u64,
+}
+
+impl ExceptionClass {
+    fn from_raw(ec: u8) -> Self {
+        match ec {
+            0x00 => Self::Unknown,
+            0x01 => Self::TrappedWfiWfe,
+            0x07 => Self::TrappedFpSimd,
+            0x0E => Self::IllegalExecutionState,
+            0x15 => Self::Svc,
+            0x16 => Self::Hvc,
+            0x17 => Self::Smc,
+            0x18 => Self::TrappedMsrMrs,
+            0x20 => Self::InstructionAbortLowerEL,
+            0x21 => Self::InstructionAbortCurrentEL,
+            0x22 => Self::PcAlignment,
+            0x24 => Self::DataAbortLowerEL,
+            0x25 => Self::DataAbortCurrentEL,
+            0x26 => Self::SpAlignment,
+            0x2C => Self::FpException,
+            0x2F => Self::SError,
+            0x30 => Self::BreakpointLowerEL,
+            0x31 => Self::BreakpointCurrentEL,
+            0x32 => Self::SoftwareStepLowerEL,
+            0x33 => Self::SoftwareStepCurrentEL,
+            0x34 => Self::WatchpointLowerEL,
+            0x35 => Self::WatchpointCurrentEL,
+            0x3C => Self::Brk,
+            x => Self::Other(x),
+        }
+    }
+
+    fn is_data_abort(self) -> bool {
+        matches!(self, Self::DataAbortLowerEL | Self::DataAbortCurrentEL)
+    }
+
+    fn is_abort(self) -> bool {
+        self.is_data_abort()
+            || matches!(
+                self,
+                Self::InstructionAbortLowerEL | Self::InstructionAbortCurrentEL
+            )
+    }
+}
+
+impl FaultStatus {
+    fn from_raw(fsc: u8) -> Self {
+        let level = fsc & 0b11;
+        match fsc {
+            0b00_0000..=0b00_0011 => Self::AddressSize(level),
+            0b00_0100..=0b00_0111 => Self::Translation(level),
+            0b00_1000..=0b00_1011 => Self::AccessFlag(level),
+            0b00_1100..=0b00_1111 => Self::Permission(level),
+            0b01_0000 => Self::SyncExternal,
+            0b01_0100..=0b01_0111 => Self::SyncExternalOnWalk(level),
+            0b01_1000 => Self::SyncParityOrEcc,
+            0b01_1100..=0b01_1111 => Self::SyncParityOrEccOnWalk(level),
+            0b10_0001 => Self::Alignment,
+            0b11_0000 => Self::TlbConflict,
+            x => Self::Other(x),
+        }
+    }
+}
+
+impl Syndrome {
+    const ISS_FSC_MASK: u64 = 0b11_1111;
+    const ISS_WNR: u64 = 1 << 6;
+    const ISS_S1PTW: u64 = 1 << 7;
+    const ISS_FNV: u64 = 1 << 10;
+
+    pub const fn new(esr: u64, far: u64) -> Self {
+        Self { esr, far }
+    }
+
+    pub fn exception_class(&self) -> ExceptionClass {
+        ExceptionClass::from_raw(((self.esr >> 26) & 0b11_1111) as u8)
+    }
+
+    /// The instruction specific syndrome, ESR_EL1[24:0].
+    pub fn iss(&self) -> u32 {
+        (self.esr & 0x1FF_FFFF) as u32
+    }
+
+    /// The decoded IFSC/DFSC, for instruction and data aborts only.
+    pub fn fault_status(&self) -> Option<FaultStatus> {
+        if !self.exception_class().is_abort() {
+            return None;
+        }
+
+        Some(FaultStatus::from_raw((self.esr & Self::ISS_FSC_MASK) as u8))
+    }
+
+    /// Whether a data abort was caused by a write (WnR), for data aborts only.
+    pub fn is_write(&self) -> Option<bool> {
+        if !self.exception_class().is_data_abort() {
+            return None;
+        }
+
+        Some(self.esr & Self::ISS_WNR != 0)
+    }
+
+    /// Whether an abort happened during a stage 1 translation table walk (S1PTW).
+    pub fn is_stage1_walk(&self) -> bool {
+        self.exception_class().is_abort() && (self.esr & Self::ISS_S1PTW != 0)
+    }
+
+    /// The faulting virtual address, if FAR_EL1 holds a valid one for this exception.
+    pub fn fault_address(&self) -> Option<u64> {
+        let far_valid = match self.exception_class() {
+            ExceptionClass::PcAlignment
+            | ExceptionClass::WatchpointLowerEL
+            | ExceptionClass::WatchpointCurrentEL => true,
+            ec if ec.is_abort() => self.esr & Self::ISS_FNV == 0,
+            _ => false,
+        };
+
+        far_valid.then_some(self.far)
+    }
+}
+
+impl fmt::Display for ExceptionClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unknown => write!(f, "Unknown reason"),
+            Self::TrappedWfiWfe => write!(f, "Trapped WFI or WFE"),
+            Self::TrappedFpSimd => write!(f, "Trapped FP/SIMD access"),
+            Self::IllegalExecutionState => write!(f, "Illegal execution state"),
+            Self::Svc => write!(f, "SVC instruction"),
+            Self::Hvc => write!(f, "HVC instruction"),
+            Self::Smc => write!(f, "SMC instruction"),
+            Self::TrappedMsrMrs => write!(f, "Trapped MSR, MRS or system instruction"),
+            Self::InstructionAbortLowerEL => write!(f, "Instruction abort from lower EL"),
+            Self::InstructionAbortCurrentEL => write!(f, "Instruction abort"),
+            Self::PcAlignment => write!(f, "PC alignment fault"),
+            Self::DataAbortLowerEL => write!(f, "Data abort from lower EL"),
+            Self::DataAbortCurrentEL => write!(f, "Data abort"),
+            Self::SpAlignment => write!(f, "SP alignment fault"),
+            Self::FpException => write!(f, "Floating-point exception"),
+            Self::SError => write!(f, "SError interrupt"),
+            Self::BreakpointLowerEL => write!(f, "Breakpoint from lower EL"),
+            Self::BreakpointCurrentEL => write!(f, "Breakpoint"),
+            Self::SoftwareStepLowerEL => write!(f, "Software step from lower EL"),
+            Self::SoftwareStepCurrentEL => write!(f, "Software step"),
+            Self::WatchpointLowerEL => write!(f, "Watchpoint from lower EL"),
+            Self::WatchpointCurrentEL => write!(f, "Watchpoint"),
+            Self::Brk => write!(f, "BRK instruction"),
+            Self::Other(x) => write!(f, "Exception class {:#04x}", x),
+        }
+    }
+}
+
+impl fmt::Display for FaultStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::AddressSize(l) => write!(f, "address size fault level {}", l),
+            Self::Translation(l) => write!(f, "translation fault level {}", l),
+            Self::AccessFlag(l) => write!(f, "access flag fault level {}", l),
+            Self::Permission(l) => write!(f, "permission fault level {}", l),
+            Self::SyncExternal => write!(f, "synchronous external abort"),
+            Self::SyncExternalOnWalk(l) => {
+                write!(f, "synchronous external abort on table walk level {}", l)
+            }
+            Self::SyncParityOrEcc => write!(f, "synchronous parity or ECC error"),
+            Self::SyncParityOrEccOnWalk(l) => {
+                write!(f, "synchronous parity or ECC error on table walk level {}", l)
+            }
+            Self::Alignment => write!(f, "alignment fault"),
+            Self::TlbConflict => write!(f, "TLB conflict abort"),
+            Self::Other(x) => write!(f, "fault status {:#04x}", x),
+        }
+    }
+}
+
+/// Prints e.g. "Data abort, translation fault level 2, write to 0x0000000000001234".
+impl fmt::Display for Syndrome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.exception_class())?;
+
+        if let Some(fault_status) = self.fault_status() {
+            write!(f, ", {}", fault_status)?;
+        }
+
+        if self.is_stage1_walk() {
+            write!(f, " during stage 1 table walk")?;
+        }
+
+        let access = match self.is_write() {
+            Some(true) => "write to",
+            Some(false) => "read from",
+            None => "at",
+        };
+
+        match self.fault_address() {
+            Some(addr) => write!(f, ", {} {:#018x}", access, addr),
+            None if self.exception_class().is_abort() => write!(f, ", fault address not valid"),
+            None => write!(f, ", ISS {:#x}", self.iss()),
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use crate::{
+    cpu::{
+        self,
+        per_cpu::PerCpu,
+        syndrome::{ExceptionClass, FaultStatus, Syndrome},
+    },
+    exception, per_cpu, thread,
+};
+use aarch64_cpu::{asm::barrier, registers::*};
+use core::{
+    arch::{asm, global_asm},
+    cell::UnsafeCell,
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use tock_registers::registers::InMemoryRegister;
+
+global_asm!(include_str!("exception.s"));
+
+/// Wrapper structs for memory copies of registers.
+#[repr(transparent)]
+struct SpsrEL1(InMemoryRegister<u64, SPSR_EL1::Register>);
+#[repr(transparent)]
+struct EsrEL1(InMemoryRegister<u64, ESR_EL1::Register>);
+
+/// The exception context as it is stored on the stack on exception entry.
+///
+/// The layout must match the one in `exception.s`.
+#[repr(C)]
+struct ExceptionContext {
+    /// General Purpose Registers x0-x29.
+    gpr: [u64; 30],
+
+    /// The link register, aka x30.
+    lr: u64,
+
+    /// Exception link register. The program counter at the time the exception happened.
+    elr_el1: u64,
+
+    /// Saved program status.
+    spsr_el1: SpsrEL1,
+
+    /// Exception syndrome register.
+    esr_el1: EsrEL1,
+
+    /// Fault address register. Only meaningful for some exception classes.
+    far_el1: u64,
+}
+
+/// The address `write_faults` of the core currently probes, or 0.
+static EXPECTED_WRITE_FAULT: PerCpu<AtomicU64> = per_cpu!(AtomicU64::new(0));
+
+/// Step over the write `write_faults` probes with, if it is what faulted.
+fn recover_expected_write_fault(e: &mut ExceptionContext) -> bool {
+    let syndrome = e.syndrome();
+    let expected = EXPECTED_WRITE_FAULT.local().load(Ordering::Relaxed);
+
+    let is_expected = expected != 0
+        && syndrome.exception_class() == ExceptionClass::DataAbortCurrentEL
+        && matches!(syndrome.fault_status(), Some(FaultStatus::Permission(_)))
+        && syndrome.is_write() == Some(true)
+        && syndrome.fault_address() == Some(expected);
+    if !is_expected {
+        return false;
+    }
+
+    EXPECTED_WRITE_FAULT.local().store(0, Ordering::Relaxed);
+    e.elr_el1 += 4;
+
+    true
+}
+
+/// Prints verbose information about the exception and then panics.
+fn default_exception_handler(exc: &ExceptionContext) {
+    panic!(
+        "CPU Exception!\n\n\
+        {}",
+        exc
+    );
+}
+
+//------------------------------------------------------------------------------
+// Current, EL0
+//------------------------------------------------------------------------------
+
+#[no_mangle]
+extern "C" fn current_el0_synchronous(_e: &mut ExceptionContext) {
+    panic!("Should not be here. Use of SP_EL0 in EL1 is not supported.")
+}
+
+#[no_mangle]
+extern "C" fn current_el0_irq(_e: &mut ExceptionContext) {
+    panic!("Should not be here. Use of SP_EL0 in EL1 is not supported.")
+}
+
+#[no_mangle]
+extern "C" fn current_el0_serror(_e: &mut ExceptionContext) {
+    panic!("Should not be here. Use of SP_EL0 in EL1 is not supported.")
+}
+
+//------------------------------------------------------------------------------
+// Current, ELx
+//------------------------------------------------------------------------------
+
+#[no_mangle]
+extern "C" fn current_elx_synchronous(e: &mut ExceptionContext) {
+    if recover_expected_write_fault(e) {
+        return;
+    }
+
+    default_exception_handler(e);
+}
+
+#[no_mangle]
+extern "C" fn current_elx_irq(_e: &mut ExceptionContext) {
+    let token = unsafe { &exception::asynchronous::IRQContext::new() };
+    exception::asynchronous::count_irq(token);
+    exception::asynchronous::irq_manager().handle_pending_irqs(token);
+    thread::preempt_on_irq_exit(token);
+}
+
+#[no_mangle]
+extern "C" fn current_elx_serror(e: &mut ExceptionContext) {
+    default_exception_handler(e);
+}
+
+//------------------------------------------------------------------------------
+// Lower, AArch64
+//------------------------------------------------------------------------------
+
+#[no_mangle]
+extern "C" fn lower_aarch64_synchronous(e: &mut ExceptionContext) {
+    default_exception_handler(e);
+}
+
+#[no_mangle]
+extern "C" fn lower_aarch64_irq(e: &mut ExceptionContext) {
+    default_exception_handler(e);
+}
+
+#[no_mangle]
+extern "C" fn lower_aarch64_serror(e: &mut ExceptionContext) {
+    default_exception_handler(e);
+}
+
+//------------------------------------------------------------------------------
+// Lower, AArch32
+//------------------------------------------------------------------------------
+
+#[no_mangle]
+extern "C" fn lower_aarch32_synchronous(e: &mut ExceptionContext) {
+    default_exception_handler(e);
+}
+
+#[no_mangle]
+extern "C" fn lower_aarch32_irq(e: &mut ExceptionContext) {
+    default_exception_handler(e);
+}
+
+#[no_mangle]
+extern "C" fn lower_aarch32_serror(e: &mut ExceptionContext) {
+    default_exception_handler(e);
+}
+
+impl fmt::Display for EsrEL1 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let esr_el1 = &self.0;
+
+        writeln!(f, "ESR_EL1: {:#010x}", esr_el1.get())?;
+
+        writeln!(f, "      Exception Class         (EC) : {:#x}", esr_el1.read(ESR_EL1::EC))?;
+        write!(f, "      Instr Specific Syndrome (ISS): {:#x}", esr_el1.read(ESR_EL1::ISS))
+    }
+}
+
+impl fmt::Display for SpsrEL1 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let spsr_el1 = &self.0;
+
+        writeln!(f, "SPSR_EL1: {:#010x}", spsr_el1.get())?;
+
+        let to_flag_str = |x| -> _ { if x { "Set" } else { "Not set" } };
+
+        writeln!(f, "      Flags:")?;
+        writeln!(f, "            Negative (N): {}", to_flag_str(spsr_el1.is_set(SPSR_EL1::N)))?;
+        writeln!(f, "            Zero     (Z): {}", to_flag_str(spsr_el1.is_set(SPSR_EL1::Z)))?;
+        writeln!(f, "            Carry    (C): {}", to_flag_str(spsr_el1.is_set(SPSR_EL1::C)))?;
+        writeln!(f, "            Overflow (V): {}", to_flag_str(spsr_el1.is_set(SPSR_EL1::V)))?;
+
+        let to_mask_str = |x| -> _ { if x { "Masked" } else { "Unmasked" } };
+
+        writeln!(f, "      Exception handling state:")?;
+        writeln!(f, "            Debug  (D): {}", to_mask_str(spsr_el1.is_set(SPSR_EL1::D)))?;
+        writeln!(f, "            SError (A): {}", to_mask_str(spsr_el1.is_set(SPSR_EL1::A)))?;
+        writeln!(f, "            IRQ    (I): {}", to_mask_str(spsr_el1.is_set(SPSR_EL1::I)))?;
+        writeln!(f, "            FIQ    (F): {}", to_mask_str(spsr_el1.is_set(SPSR_EL1::F)))?;
+
+        write!(
+            f,
+            "      Illegal Execution State (IL): {}",
+            to_flag_str(spsr_el1.is_set(SPSR_EL1::IL))
+        )
+    }
+}
+
+impl ExceptionContext {
+    fn syndrome(&self) -> Syndrome {
+        Syndrome::new(self.esr_el1.0.get(), self.far_el1)
+    }
+}
+
+impl fmt::Display for ExceptionContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.syndrome())?;
+        writeln!(f)?;
+        writeln!(f, "{}", self.esr_el1)?;
+        writeln!(f, "FAR_EL1: {:#018x}", self.far_el1)?;
+        writeln!(f, "ELR_EL1: {:#018x}", self.elr_el1)?;
+        writeln!(f, "{}", self.spsr_el1)?;
+        writeln!(f)?;
+        writeln!(f, "General purpose register:")?;
+
+        let alternating = |x| -> _ { if x % 2 == 0 { "   " } else { "\n" } };
+
+        for (i, reg) in self.gpr.iter().enumerate() {
+            write!(f, "      x{: <2}: {: >#018x}{}", i, reg, alternating(i))?;
+        }
+        write!(f, "      lr : {:#018x}", self.lr)
+    }
+}
+
+/// Install the exception vector table for the executing core.
+///
+/// # Safety
+///
+/// - Changes the HW state of the executing core.
+pub unsafe fn handling_init() {
+    // Provided by exception.s.
+    extern "Rust" {
+        static __exception_vector_start: UnsafeCell<()>;
+    }
+
+    VBAR_EL1.set(__exception_vector_start.get() as u64);
+
+    // Force VBAR update to complete before the next instruction.
+    barrier::isb(barrier::SY);
+}
+
+/// Whether writing to `addr` takes a permission fault.
+///
+/// The byte at `addr` is written back unchanged, so nothing changes if the write succeeds.
+///
+/// # Safety
+///
+/// - `addr` must be mapped readable.
+/// - Exception handling must be initialized on the executing core.
+pub unsafe fn write_faults(addr: *mut u8) -> bool {
+    cpu::exec_with_irq_masked(|| {
+        let expected = EXPECTED_WRITE_FAULT.local();
+        let value = core::ptr::read_volatile(addr);
+
+        expected.store(addr as u64, Ordering::Relaxed);
+
+        // A single instruction, so the handler can step over it.
+        asm!(
+            "strb {value:w}, [{addr}]",
+            value = in(reg) value as u32,
+            addr = in(reg) addr,
+            options(nostack)
+        );
+
+        expected.swap(0, Ordering::Relaxed) == 0
+    })
+}
@@ -0,0 +1,18 @@
+use crate::info;
+use aarch64_cpu::registers::*;
+
+fn to_mask_str(masked: bool) -> &'static str {
+    if masked {
+        "Masked"
+    } else {
+        "Unmasked"
+    }
+}
+
+/// Print the AArch64 exceptions status of the executing core.
+pub fn print_state() {
+    info!("      Debug:  {}", to_mask_str(DAIF.is_set(DAIF::D)));
+    info!("      SError: {}", to_mask_str(DAIF.is_set(DAIF::A)));
+    info!("      IRQ:    {}", to_mask_str(DAIF.is_set(DAIF::I)));
+    info!("      FIQ:    {}", to_mask_str(DAIF.is_set(DAIF::F)));
+}
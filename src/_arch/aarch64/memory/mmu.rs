@@ -0,0 +1,137 @@
+//! Memory management unit driver, using the 64 KiB translation granule.
+//!
+//! The kernel's address space is translated through TTBR1_EL1. TTBR0_EL1 only holds an identity map
+//! while booting.
+
+use crate::{
+    bsp,
+    memory::mmu::{
+        interface, translation_table::KernelTranslationTable, MMUEnableError, TranslationGranule,
+    },
+};
+use aarch64_cpu::{asm::barrier, registers::*};
+use tock_registers::interfaces::{ReadWriteable, Readable, Writeable};
+
+struct MemoryManagementUnit;
+
+pub type Granule512MiB = TranslationGranule<{ 512 * 1024 * 1024 }>;
+pub type Granule64KiB = TranslationGranule<{ 64 * 1024 }>;
+
+/// Indices of the memory attributes in MAIR_EL1.
+pub mod mair {
+    pub const DEVICE: u64 = 0;
+    pub const NORMAL: u64 = 1;
+}
+
+/// Filled in the kernel ELF by `tools/translation_table_tool` at build time, so it must be in
+/// `.data` and keep its name, which `boot.s` uses too. Only read afterwards.
+#[no_mangle]
+#[link_section = ".data"]
+static mut KERNEL_TABLES: KernelTranslationTable = KernelTranslationTable::new();
+
+static MMU: MemoryManagementUnit = MemoryManagementUnit;
+
+pub fn mmu() -> &'static impl interface::MMU {
+    &MMU
+}
+
+/// Check that the precomputed translation tables match the BSP's `KernelVirtualLayout`.
+pub fn verify_translation_tables() -> Result<(), &'static str> {
+    let tables = unsafe { &*core::ptr::addr_of!(KERNEL_TABLES) };
+
+    tables.verify_tt_entries()
+}
+
+impl MemoryManagementUnit {
+    fn set_up_mair(&self) {
+        MAIR_EL1.write(
+            MAIR_EL1::Attr1_Normal_Outer::WriteBack_NonTransient_ReadWriteAlloc
+                + MAIR_EL1::Attr1_Normal_Inner::WriteBack_NonTransient_ReadWriteAlloc
+                + MAIR_EL1::Attr0_Device::nonGathering_nonReordering_EarlyWriteAck,
+        );
+    }
+
+    /// Both halves have the size of the kernel's address space. The kernel's tables translate
+    /// only the offset into a half, so they map the top half to the physical address space and
+    /// double as the identity map in the bottom one.
+    fn configure_translation_control(&self) {
+        let txsz = (64 - bsp::memory::mmu::KernelAddrSpace::SIZE_SHIFT) as u64;
+
+        TCR_EL1.write(
+            TCR_EL1::TBI0::Used
+                + TCR_EL1::TBI1::Used
+                + TCR_EL1::IPS::Bits_40
+                + TCR_EL1::TG0::KiB_64
+                + TCR_EL1::TG1::KiB_64
+                + TCR_EL1::SH0::Inner
+                + TCR_EL1::SH1::Inner
+                + TCR_EL1::ORGN0::WriteBack_ReadAlloc_WriteAlloc_Cacheable
+                + TCR_EL1::IRGN0::WriteBack_ReadAlloc_WriteAlloc_Cacheable
+                + TCR_EL1::ORGN1::WriteBack_ReadAlloc_WriteAlloc_Cacheable
+                + TCR_EL1::IRGN1::WriteBack_ReadAlloc_WriteAlloc_Cacheable
+                + TCR_EL1::EPD0::EnableTTBR0Walks
+                + TCR_EL1::EPD1::EnableTTBR1Walks
+                + TCR_EL1::A1::TTBR0
+                + TCR_EL1::T0SZ.val(txsz)
+                + TCR_EL1::T1SZ.val(txsz),
+        );
+    }
+}
+
+impl interface::MMU for MemoryManagementUnit {
+    unsafe fn enable_mmu_and_caching(
+        &self,
+        phys_tables_base_addr: usize,
+    ) -> Result<(), MMUEnableError> {
+        if self.is_enabled() {
+            return Err(MMUEnableError::AlreadyEnabled);
+        }
+
+        if !ID_AA64MMFR0_EL1.matches_all(ID_AA64MMFR0_EL1::TGran64::Supported) {
+            return Err(MMUEnableError::Other(
+                "Translation granule not supported in HW",
+            ));
+        }
+
+        // Read through the physical address, the MMU is still off.
+        let tables = &*(phys_tables_base_addr as *const KernelTranslationTable);
+        if !tables.is_populated() {
+            return Err(MMUEnableError::Other(
+                "Translation tables were not precomputed",
+            ));
+        }
+
+        self.set_up_mair();
+        TTBR0_EL1.set_baddr(tables.phys_base_address());
+        TTBR1_EL1.set_baddr(tables.phys_base_address());
+        self.configure_translation_control();
+
+        // Nothing stale may be left in the TLBs before the MMU starts walking.
+        barrier::dsb(barrier::ISH);
+        core::arch::asm!("tlbi vmalle1", options(nostack));
+        barrier::dsb(barrier::ISH);
+        barrier::isb(barrier::SY);
+
+        SCTLR_EL1.modify(SCTLR_EL1::M::Enable + SCTLR_EL1::C::Cacheable + SCTLR_EL1::I::Cacheable);
+
+        barrier::isb(barrier::SY);
+
+        Ok(())
+    }
+
+    unsafe fn disable_identity_map(&self) {
+        TCR_EL1.modify(TCR_EL1::EPD0::DisableTTBR0Walks);
+        TTBR0_EL1.set(0);
+        barrier::isb(barrier::SY);
+
+        // The walks are off, but the TLBs may still hold identity map entries.
+        core::arch::asm!("tlbi vmalle1", options(nostack));
+        barrier::dsb(barrier::NSH);
+        barrier::isb(barrier::SY);
+    }
+
+    #[inline(always)]
+    fn is_enabled(&self) -> bool {
+        SCTLR_EL1.matches_all(SCTLR_EL1::M::Enable)
+    }
+}
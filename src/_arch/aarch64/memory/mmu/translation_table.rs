@@ -0,0 +1,258 @@
+//! Translation tables for the 64 KiB granule.
+//!
+//! Two levels: every level 2 entry covers 512 MiB and points to a level 3 table of 64 KiB pages.
+//! Entry n maps the n-th page of a TTBR's half of the address space to physical page n.
+//!
+//! The kernel doesn't build its tables. `tools/translation_table_tool` computes them from the
+//! kernel ELF at build time, following the same layout and descriptor format as this file.
+
+use crate::{
+    bsp,
+    memory::mmu::{
+        arch_mmu::{mair, Granule512MiB, Granule64KiB},
+        AccessPermissions, AttributeFields, MemAttributes,
+    },
+    warn,
+};
+use tock_registers::{
+    fields::FieldValue,
+    interfaces::{Readable, Writeable},
+    register_bitfields,
+    registers::InMemoryRegister,
+};
+
+// A table descriptor, as per ARMv8-A Architecture Reference Manual Figure D5-15.
+register_bitfields! {u64,
+    STAGE1_TABLE_DESCRIPTOR [
+        /// Physical address of the next table descriptor.
+        NEXT_LEVEL_TABLE_ADDR_64KiB OFFSET(16) NUMBITS(32) [],
+
+        TYPE OFFSET(1) NUMBITS(1) [
+            Block = 0,
+            Table = 1
+        ],
+
+        VALID OFFSET(0) NUMBITS(1) [
+            False = 0,
+            True = 1
+        ]
+    ]
+}
+
+// A level 3 page descriptor, as per ARMv8-A Architecture Reference Manual Figure D5-17.
+register_bitfields! {u64,
+    STAGE1_PAGE_DESCRIPTOR [
+        /// Unprivileged execute-never.
+        UXN OFFSET(54) NUMBITS(1) [
+            False = 0,
+            True = 1
+        ],
+
+        /// Privileged execute-never.
+        PXN OFFSET(53) NUMBITS(1) [
+            False = 0,
+            True = 1
+        ],
+
+        /// Physical address of the next table descriptor (lvl2) or the page descriptor (lvl3).
+        OUTPUT_ADDR_64KiB OFFSET(16) NUMBITS(32) [],
+
+        /// Access flag.
+        AF OFFSET(10) NUMBITS(1) [
+            False = 0,
+            True = 1
+        ],
+
+        /// Shareability field.
+        SH OFFSET(8) NUMBITS(2) [
+            OuterShareable = 0b10,
+            InnerShareable = 0b11
+        ],
+
+        /// Access Permissions.
+        AP OFFSET(6) NUMBITS(2) [
+            RW_EL1 = 0b00,
+            RW_EL1_EL0 = 0b01,
+            RO_EL1 = 0b10,
+            RO_EL1_EL0 = 0b11
+        ],
+
+        /// Memory attributes index into the MAIR_EL1 register.
+        AttrIndx OFFSET(2) NUMBITS(3) [],
+
+        TYPE OFFSET(1) NUMBITS(1) [
+            Reserved_Invalid = 0,
+            Page = 1
+        ],
+
+        VALID OFFSET(0) NUMBITS(1) [
+            False = 0,
+            True = 1
+        ]
+    ]
+}
+
+/// Entries of a level 3 table.
+const NUM_LVL3_ENTRIES: usize = Granule512MiB::SIZE >> Granule64KiB::SHIFT;
+
+/// Level 2 entries needed for the kernel's address space.
+const NUM_LVL2_TABLES: usize = bsp::memory::mmu::KernelAddrSpace::SIZE >> Granule512MiB::SHIFT;
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+struct TableDescriptor {
+    value: u64,
+}
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+struct PageDescriptor {
+    value: u64,
+}
+
+/// Level 3 tables first, so that they are aligned to 64 KiB as well.
+#[repr(C, align(65536))]
+pub struct FixedSizeTranslationTable<const NUM_TABLES: usize> {
+    lvl3: [[PageDescriptor; NUM_LVL3_ENTRIES]; NUM_TABLES],
+    lvl2: [TableDescriptor; NUM_TABLES],
+}
+
+/// A translation table covering the kernel's address space.
+pub type KernelTranslationTable = FixedSizeTranslationTable<NUM_LVL2_TABLES>;
+
+impl TableDescriptor {
+    const fn new_zeroed() -> Self {
+        Self { value: 0 }
+    }
+
+    fn from_next_lvl_table_addr(phys_next_lvl_table_addr: usize) -> Self {
+        let val = InMemoryRegister::<u64, STAGE1_TABLE_DESCRIPTOR::Register>::new(0);
+
+        let shifted = phys_next_lvl_table_addr >> Granule64KiB::SHIFT;
+        val.write(
+            STAGE1_TABLE_DESCRIPTOR::NEXT_LEVEL_TABLE_ADDR_64KiB.val(shifted as u64)
+                + STAGE1_TABLE_DESCRIPTOR::TYPE::Table
+                + STAGE1_TABLE_DESCRIPTOR::VALID::True,
+        );
+
+        Self { value: val.get() }
+    }
+
+    fn is_valid(&self) -> bool {
+        InMemoryRegister::<u64, STAGE1_TABLE_DESCRIPTOR::Register>::new(self.value)
+            .is_set(STAGE1_TABLE_DESCRIPTOR::VALID)
+    }
+}
+
+impl From<AttributeFields> for FieldValue<u64, STAGE1_PAGE_DESCRIPTOR::Register> {
+    fn from(attribute_fields: AttributeFields) -> Self {
+        let mut desc = match attribute_fields.mem_attributes {
+            MemAttributes::CacheableDRAM => {
+                STAGE1_PAGE_DESCRIPTOR::SH::InnerShareable
+                    + STAGE1_PAGE_DESCRIPTOR::AttrIndx.val(mair::NORMAL)
+            }
+            MemAttributes::Device => {
+                STAGE1_PAGE_DESCRIPTOR::SH::OuterShareable
+                    + STAGE1_PAGE_DESCRIPTOR::AttrIndx.val(mair::DEVICE)
+            }
+        };
+
+        desc += match attribute_fields.acc_perms {
+            AccessPermissions::ReadOnly => STAGE1_PAGE_DESCRIPTOR::AP::RO_EL1,
+            AccessPermissions::ReadWrite => STAGE1_PAGE_DESCRIPTOR::AP::RW_EL1,
+        };
+
+        desc += if attribute_fields.execute_never {
+            STAGE1_PAGE_DESCRIPTOR::PXN::True
+        } else {
+            STAGE1_PAGE_DESCRIPTOR::PXN::False
+        };
+
+        // The kernel has no EL0 code yet.
+        desc += STAGE1_PAGE_DESCRIPTOR::UXN::True;
+
+        desc
+    }
+}
+
+impl PageDescriptor {
+    const fn new_zeroed() -> Self {
+        Self { value: 0 }
+    }
+
+    fn from_output_addr(phys_output_addr: usize, attribute_fields: AttributeFields) -> Self {
+        let val = InMemoryRegister::<u64, STAGE1_PAGE_DESCRIPTOR::Register>::new(0);
+
+        let shifted = phys_output_addr >> Granule64KiB::SHIFT;
+        val.write(
+            STAGE1_PAGE_DESCRIPTOR::OUTPUT_ADDR_64KiB.val(shifted as u64)
+                + STAGE1_PAGE_DESCRIPTOR::AF::True
+                + STAGE1_PAGE_DESCRIPTOR::TYPE::Page
+                + STAGE1_PAGE_DESCRIPTOR::VALID::True
+                + attribute_fields.into(),
+        );
+
+        Self { value: val.get() }
+    }
+}
+
+impl<const NUM_TABLES: usize> FixedSizeTranslationTable<NUM_TABLES> {
+    pub const fn new() -> Self {
+        assert!(NUM_TABLES > 0);
+
+        Self {
+            lvl3: [[PageDescriptor::new_zeroed(); NUM_LVL3_ENTRIES]; NUM_TABLES],
+            lvl2: [TableDescriptor::new_zeroed(); NUM_TABLES],
+        }
+    }
+
+    /// Whether the tables were filled in. A table that wasn't is all zeroes.
+    pub fn is_populated(&self) -> bool {
+        self.lvl2.iter().all(TableDescriptor::is_valid)
+    }
+
+    /// Check that every page is mapped with the attributes the BSP's layout gives its kernel virtual
+    /// address, and that pages without attributes are invalid.
+    ///
+    /// Must be called through the kernel's virtual address of `self`.
+    pub fn verify_tt_entries(&self) -> Result<(), &'static str> {
+        for (l2_nr, l2_entry) in self.lvl2.iter().enumerate() {
+            let lvl3_phys_addr = bsp::memory::virt_to_phys(self.lvl3[l2_nr].as_ptr() as usize);
+            let expected = TableDescriptor::from_next_lvl_table_addr(lvl3_phys_addr);
+            if l2_entry.value != expected.value {
+                warn!(
+                    "Level 2 entry {} is {:#018x}, expected {:#018x}",
+                    l2_nr, l2_entry.value, expected.value
+                );
+                return Err("Level 2 table does not point to the level 3 tables");
+            }
+
+            for (l3_nr, l3_entry) in self.lvl3[l2_nr].iter().enumerate() {
+                let addr = (l2_nr << Granule512MiB::SHIFT) + (l3_nr << Granule64KiB::SHIFT);
+
+                let virt_addr = bsp::memory::phys_to_virt(addr);
+
+                let expected =
+                    match bsp::memory::mmu::virt_mem_layout().virt_addr_properties(virt_addr) {
+                        Some(attributes) => PageDescriptor::from_output_addr(addr, attributes),
+                        None => PageDescriptor::new_zeroed(),
+                    };
+                if l3_entry.value != expected.value {
+                    warn!(
+                        "Page {:#018x} is mapped by {:#018x}, expected {:#018x}",
+                        virt_addr, l3_entry.value, expected.value
+                    );
+                    return Err("Level 3 tables do not match the kernel's virtual layout");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The address to put into the TTBRs. Physical only if `self` is accessed through its physical
+    /// address.
+    pub fn phys_base_address(&self) -> u64 {
+        self.lvl2.as_ptr() as u64
+    }
+}
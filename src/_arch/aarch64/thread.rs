@@ -0,0 +1,54 @@
+//! Architectural thread context switching.
+//!
+//! The kernel is built for a softfloat target, so only the callee-saved general purpose registers
+//! and the stack pointer make up a thread's context.
+
+use core::arch::global_asm;
+
+global_asm!(include_str!("thread.s"));
+
+/// The callee-saved registers of a thread that is not running.
+#[repr(C)]
+pub struct Context {
+    /// x19 to x29.
+    gpr: [u64; 11],
+    lr: u64,
+    sp: u64,
+}
+
+extern "C" {
+    fn __context_switch(current: *mut Context, next: *const Context);
+    fn __thread_start();
+}
+
+impl Context {
+    pub const fn new() -> Self {
+        Self {
+            gpr: [0; 11],
+            lr: 0,
+            sp: 0,
+        }
+    }
+
+    /// A context that starts executing `crate::thread::thread_start(entry)` on the given stack.
+    pub fn new_thread(stack_end_exclusive: usize, entry: fn()) -> Self {
+        let mut ctx = Self::new();
+
+        // `__thread_start` hands x19 over as the first argument.
+        ctx.gpr[0] = entry as *const () as u64;
+        ctx.lr = __thread_start as *const () as u64;
+        ctx.sp = stack_end_exclusive as u64;
+
+        ctx
+    }
+}
+
+/// Save the executing thread's context into `current` and resume the one in `next`.
+///
+/// # Safety
+///
+/// - `next` must hold a context created by `Context::new_thread` or saved by a previous switch.
+/// - Both must stay valid until the respective thread runs again.
+pub unsafe fn context_switch(current: *mut Context, next: *const Context) {
+    __context_switch(current, next)
+}
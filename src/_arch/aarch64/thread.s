@@ -0,0 +1,35 @@
+// void __context_switch(Context *current, const Context *next)
+__context_switch:
+    stp x19, x20, [x0, #16 * 0]
+    stp x21, x22, [x0, #16 * 1]
+    stp x23, x24, [x0, #16 * 2]
+    stp x25, x26, [x0, #16 * 3]
+    stp x27, x28, [x0, #16 * 4]
+    stp x29, x30, [x0, #16 * 5]
+    mov x9, sp
+    str x9, [x0, #16 * 6]
+
+    ldp x19, x20, [x1, #16 * 0]
+    ldp x21, x22, [x1, #16 * 1]
+    ldp x23, x24, [x1, #16 * 2]
+    ldp x25, x26, [x1, #16 * 3]
+    ldp x27, x28, [x1, #16 * 4]
+    ldp x29, x30, [x1, #16 * 5]
+    ldr x9, [x1, #16 * 6]
+    mov sp, x9
+
+    ret
+
+.size __context_switch, . - __context_switch
+.type __context_switch, function
+.global __context_switch
+
+// First code of a new thread, reached through the `ret` of `__context_switch`.
+__thread_start:
+    mov x0, x19
+    mov x29, xzr
+    b thread_start
+
+.size __thread_start, . - __thread_start
+.type __thread_start, function
+.global __thread_start
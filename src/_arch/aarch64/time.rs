@@ -5,7 +5,7 @@ use core::{
     ops::{ Add, Div },
     time::Duration,
 };
-use tock_registers::interfaces::Readable;
+use tock_registers::interfaces::{Readable, Writeable};
 
 //const NANOSEC_PER_SEC: u64 = 1_000_000_000;
 const NANOSEC_PER_SEC: NonZeroU64 = NonZeroU64::new(1_000_000_000).unwrap();
@@ -106,3 +106,21 @@ pub fn spin_for(duration: Duration) {
     
     while GenericTimerCounterValue(CNTPCT_EL0.get()) < counter_value_target {}
 }
+
+/// Let the EL1 physical timer raise an interrupt once the uptime reaches `deadline`. Interrupts
+/// right away if it already passed.
+pub fn set_compare(deadline: Duration) {
+    // One more, since the conversion rounds down and the interrupt must not come early.
+    let counter_value = match GenericTimerCounterValue::try_from(deadline) {
+        Ok(val) if val < GenericTimerCounterValue::MAX => val + GenericTimerCounterValue(1),
+        _ => GenericTimerCounterValue::MAX,
+    };
+
+    CNTP_CVAL_EL0.set(counter_value.0);
+    CNTP_CTL_EL0.write(CNTP_CTL_EL0::ENABLE::SET + CNTP_CTL_EL0::IMASK::CLEAR);
+}
+
+/// Stop the EL1 physical timer from interrupting.
+pub fn disable_compare() {
+    CNTP_CTL_EL0.write(CNTP_CTL_EL0::ENABLE::CLEAR);
+}
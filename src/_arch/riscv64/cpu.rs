@@ -1,4 +1,5 @@
-use riscv::asm;
+use crate::cpu::PrivilegeLevel;
+use riscv::{asm, interrupt, register::mstatus};
 
 pub use asm::nop;
 
@@ -9,9 +10,71 @@ pub fn spin_for_cycles(n: usize) {
     }
 }
 
+/// Sleep until an interrupt is pending, even if it is masked.
+#[inline(always)]
+pub fn wait_for_interrupt() {
+    asm::wfi();
+}
+
 #[inline(always)]
 pub fn wait_forever() -> ! {
     loop {
         asm::wfi();
     }
 }
+
+/// Whether atomic read-modify-write operations work on the executing hart.
+///
+/// The A extension's instructions don't depend on any setup.
+#[inline(always)]
+pub fn exclusives_usable() -> bool {
+    true
+}
+
+/// The hart's current privilege mode, plus its name for printing.
+///
+/// RISC-V has no CSR that reports the current mode, so this cannot be determined yet.
+pub fn current_privilege_level() -> (PrivilegeLevel, &'static str) {
+    (PrivilegeLevel::Unknown, "Unknown")
+}
+
+/// Enable machine-mode interrupts on the executing hart.
+///
+/// # Safety
+///
+/// - Changes the HW state of the executing hart.
+#[inline(always)]
+pub unsafe fn local_irq_unmask() {
+    interrupt::enable();
+}
+
+/// Disable machine-mode interrupts on the executing hart.
+///
+/// # Safety
+///
+/// - Changes the HW state of the executing hart.
+#[inline(always)]
+pub unsafe fn local_irq_mask() {
+    interrupt::disable();
+}
+
+#[inline(always)]
+fn is_local_irq_masked() -> bool {
+    !mstatus::read().mie()
+}
+
+/// Execute `f` with interrupts disabled on the executing hart, restoring the previous state
+/// afterwards.
+#[inline(always)]
+pub fn exec_with_irq_masked<T>(f: impl FnOnce() -> T) -> T {
+    let was_masked = is_local_irq_masked();
+    unsafe { local_irq_mask() };
+
+    let ret = f();
+
+    if !was_masked {
+        unsafe { local_irq_unmask() };
+    }
+
+    ret
+}
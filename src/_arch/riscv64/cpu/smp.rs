@@ -0,0 +1,29 @@
+use core::ptr;
+use riscv::register::mhartid;
+
+/// The MSIP registers of the CLINT on QEMU's `virt` machine, one 32-bit word per hart. Writing 1
+/// raises a machine software interrupt on the hart, writing 0 clears it.
+const CLINT_MSIP_BASE: usize = 0x0200_0000;
+
+/// Id of the executing hart.
+#[inline(always)]
+pub fn core_id() -> usize {
+    mhartid::read()
+}
+
+fn msip(hart: usize) -> *mut u32 {
+    (CLINT_MSIP_BASE + 4 * hart) as *mut u32
+}
+
+/// Raise a machine software interrupt on `hart`.
+///
+/// Cross-core calls don't use this yet. That needs trap handling on riscv64, and harts that leave
+/// the parking loop in `boot.s`.
+pub fn send_ipi(hart: usize) {
+    unsafe { ptr::write_volatile(msip(hart), 1) };
+}
+
+/// Clear the machine software interrupt of the executing hart.
+pub fn clear_ipi() {
+    unsafe { ptr::write_volatile(msip(core_id()), 0) };
+}
@@ -0,0 +1,51 @@
+//! Architectural thread context switching.
+
+use core::arch::global_asm;
+
+global_asm!(include_str!("thread.s"));
+
+/// The callee-saved registers of a thread that is not running.
+#[repr(C)]
+pub struct Context {
+    ra: u64,
+    sp: u64,
+    /// s0 to s11.
+    s: [u64; 12],
+}
+
+extern "C" {
+    fn __context_switch(current: *mut Context, next: *const Context);
+    fn __thread_start();
+}
+
+impl Context {
+    pub const fn new() -> Self {
+        Self {
+            ra: 0,
+            sp: 0,
+            s: [0; 12],
+        }
+    }
+
+    /// A context that starts executing `crate::thread::thread_start(entry)` on the given stack.
+    pub fn new_thread(stack_end_exclusive: usize, entry: fn()) -> Self {
+        let mut ctx = Self::new();
+
+        // `__thread_start` hands s1 over as the first argument.
+        ctx.s[1] = entry as *const () as u64;
+        ctx.ra = __thread_start as *const () as u64;
+        ctx.sp = stack_end_exclusive as u64;
+
+        ctx
+    }
+}
+
+/// Save the executing thread's context into `current` and resume the one in `next`.
+///
+/// # Safety
+///
+/// - `next` must hold a context created by `Context::new_thread` or saved by a previous switch.
+/// - Both must stay valid until the respective thread runs again.
+pub unsafe fn context_switch(current: *mut Context, next: *const Context) {
+    __context_switch(current, next)
+}
@@ -0,0 +1,47 @@
+# void __context_switch(Context *current, const Context *next)
+__context_switch:
+    sd ra, 0(a0)
+    sd sp, 8(a0)
+    sd s0, 16(a0)
+    sd s1, 24(a0)
+    sd s2, 32(a0)
+    sd s3, 40(a0)
+    sd s4, 48(a0)
+    sd s5, 56(a0)
+    sd s6, 64(a0)
+    sd s7, 72(a0)
+    sd s8, 80(a0)
+    sd s9, 88(a0)
+    sd s10, 96(a0)
+    sd s11, 104(a0)
+
+    ld ra, 0(a1)
+    ld sp, 8(a1)
+    ld s0, 16(a1)
+    ld s1, 24(a1)
+    ld s2, 32(a1)
+    ld s3, 40(a1)
+    ld s4, 48(a1)
+    ld s5, 56(a1)
+    ld s6, 64(a1)
+    ld s7, 72(a1)
+    ld s8, 80(a1)
+    ld s9, 88(a1)
+    ld s10, 96(a1)
+    ld s11, 104(a1)
+
+    ret
+
+.size __context_switch, . - __context_switch
+.type __context_switch, function
+.global __context_switch
+
+# First code of a new thread, reached through the `ret` of `__context_switch`.
+__thread_start:
+    mv a0, s1
+    mv s0, zero
+    j thread_start
+
+.size __thread_start, . - __thread_start
+.type __thread_start, function
+.global __thread_start
@@ -0,0 +1,59 @@
+use crate::cpu::smp;
+use core::time::Duration;
+
+const NANOSEC_PER_SEC: u64 = 1_000_000_000;
+
+/// Frequency of `mtime` on QEMU's virt machine.
+const TIMEBASE_FREQUENCY: u64 = 10_000_000;
+
+/// The CLINT of QEMU's virt machine, holding `mtime` and one `mtimecmp` per hart.
+const CLINT_MTIMECMP_BASE: usize = 0x0200_4000;
+const CLINT_MTIME: usize = 0x0200_bff8;
+
+fn read_mtime() -> u64 {
+    unsafe { core::ptr::read_volatile(CLINT_MTIME as *const u64) }
+}
+
+fn write_mtimecmp(value: u64) {
+    let mtimecmp = (CLINT_MTIMECMP_BASE + 8 * smp::core_id()) as *mut u64;
+    unsafe { core::ptr::write_volatile(mtimecmp, value) }
+}
+
+fn to_duration(counter_value: u64) -> Duration {
+    let secs = counter_value / TIMEBASE_FREQUENCY;
+    let nanos = (counter_value % TIMEBASE_FREQUENCY) * NANOSEC_PER_SEC / TIMEBASE_FREQUENCY;
+
+    Duration::new(secs, nanos as u32)
+}
+
+/// Rounded up, saturating at `u64::MAX`.
+fn to_counter_value(d: Duration) -> u64 {
+    let counter_value = (d.as_nanos() * TIMEBASE_FREQUENCY as u128).div_ceil(NANOSEC_PER_SEC as u128);
+
+    u64::try_from(counter_value).unwrap_or(u64::MAX)
+}
+
+pub fn resolution() -> Duration {
+    to_duration(1)
+}
+
+pub fn uptime() -> Duration {
+    to_duration(read_mtime())
+}
+
+pub fn spin_for(duration: Duration) {
+    let target = read_mtime().saturating_add(to_counter_value(duration));
+
+    while read_mtime() < target {}
+}
+
+/// Let the machine timer of the executing hart interrupt once the uptime reaches `deadline`.
+/// Interrupts right away if it already passed.
+pub fn set_compare(deadline: Duration) {
+    write_mtimecmp(to_counter_value(deadline));
+}
+
+/// Stop the machine timer of the executing hart from interrupting.
+pub fn disable_compare() {
+    write_mtimecmp(u64::MAX);
+}
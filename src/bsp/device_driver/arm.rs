@@ -0,0 +1,3 @@
+mod gicv2;
+
+pub use gicv2::*;
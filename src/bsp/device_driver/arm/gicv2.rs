@@ -0,0 +1,164 @@
+//! GICv2 Driver - ARM Generic Interrupt Controller v2.
+//!
+//! The GIC-400 in the BCM2711 implements version 2 of the GIC architecture. The distributor
+//! (GICD) takes care of prioritizing and routing interrupts to cores, while every core has its
+//! own CPU interface (GICC) for acknowledging and completing them.
+
+mod gicc;
+mod gicd;
+
+use crate::{
+    bsp::device_driver::common::BoundedUsize,
+    driver, exception, info,
+    synchronization::{interface::Mutex, IRQSafeSpinLock},
+};
+
+type HandlerTable = [Option<exception::asynchronous::IRQHandlerDescriptor<IRQNumber>>;
+    IRQNumber::MAX_INCLUSIVE + 1];
+
+/// Used for the associated type of trait [`exception::asynchronous::interface::IRQManager`].
+pub type IRQNumber = BoundedUsize<{ GICv2::MAX_IRQ_NUMBER }>;
+
+/// Representation of the GIC.
+pub struct GICv2 {
+    /// The Distributor.
+    gicd: gicd::GICD,
+
+    /// The CPU Interface.
+    gicc: gicc::GICC,
+
+    /// Stores registered IRQ handlers.
+    handler_table: IRQSafeSpinLock<HandlerTable>,
+}
+
+impl GICv2 {
+    // The architectural maximum is 1019, but the BCM2711 only implements 256 SPIs, plus the 32
+    // private interrupts.
+    const MAX_IRQ_NUMBER: usize = 287;
+
+    /// IRQs 0 to 15 are the software generated interrupts.
+    const MAX_SGI_NUMBER: usize = 15;
+
+    pub const COMPATIBLE: &'static str = "GICv2 (ARM Generic Interrupt Controller v2)";
+
+    pub const unsafe fn new(gicd_mmio_start_addr: usize, gicc_mmio_start_addr: usize) -> Self {
+        Self {
+            gicd: gicd::GICD::new(gicd_mmio_start_addr),
+            gicc: gicc::GICC::new(gicc_mmio_start_addr),
+            handler_table: IRQSafeSpinLock::new([None; IRQNumber::MAX_INCLUSIVE + 1]),
+        }
+    }
+}
+
+impl driver::interface::DeviceDriver for GICv2 {
+    type IRQNumberType = IRQNumber;
+
+    fn compatible(&self) -> &'static str {
+        Self::COMPATIBLE
+    }
+
+    unsafe fn init(&self) -> Result<(), &'static str> {
+        self.gicd.boot_core_init();
+
+        self.gicc.priority_accept_all();
+        self.gicc.enable();
+
+        Ok(())
+    }
+
+    unsafe fn init_secondary_core(&self) -> Result<(), &'static str> {
+        // The distributor is already up, only the core's own CPU interface is left.
+        self.gicc.priority_accept_all();
+        self.gicc.enable();
+
+        Ok(())
+    }
+}
+
+impl exception::asynchronous::interface::IRQManager for GICv2 {
+    type IRQNumberType = IRQNumber;
+
+    fn register_handler(
+        &self,
+        irq_handler_descriptor: exception::asynchronous::IRQHandlerDescriptor<Self::IRQNumberType>,
+    ) -> Result<(), &'static str> {
+        self.handler_table.lock(|table| {
+            let irq_number = irq_handler_descriptor.number().get();
+
+            if table[irq_number].is_some() {
+                return Err("IRQ handler already registered");
+            }
+
+            table[irq_number] = Some(irq_handler_descriptor);
+
+            Ok(())
+        })
+    }
+
+    fn enable(&self, irq_number: &Self::IRQNumberType) -> Result<(), &'static str> {
+        self.gicd.enable(irq_number);
+
+        Ok(())
+    }
+
+    fn disable(&self, irq_number: &Self::IRQNumberType) -> Result<(), &'static str> {
+        self.gicd.disable(irq_number);
+
+        Ok(())
+    }
+
+    fn send_ipi(
+        &self,
+        irq_number: &Self::IRQNumberType,
+        target_core: usize,
+    ) -> Result<(), &'static str> {
+        if irq_number.get() > GICv2::MAX_SGI_NUMBER {
+            return Err("Only SGIs can be sent as IPIs");
+        }
+        if target_core >= 8 {
+            return Err("No such core");
+        }
+
+        self.gicd.send_sgi(irq_number.get(), target_core);
+
+        Ok(())
+    }
+
+    fn handle_pending_irqs<'irq_context>(
+        &'irq_context self,
+        ic: &exception::asynchronous::IRQContext<'irq_context>,
+    ) {
+        // Acknowledge the highest priority pending IRQ through the Interrupt Acknowledge Register
+        // (IAR).
+        let irq = self.gicc.acknowledge(ic);
+        let irq_number = irq.number();
+
+        // Guard against spurious interrupts.
+        if irq_number > GICv2::MAX_IRQ_NUMBER {
+            return;
+        }
+
+        // Call the IRQ handler. Panic if there is none. Don't hold the lock while the handler
+        // runs, other cores might need the table.
+        match self.handler_table.lock(|table| table[irq_number]) {
+            None => panic!("No handler registered for IRQ {}", irq_number),
+            Some(descriptor) => {
+                // Call the IRQ handler. Panics on failure.
+                descriptor.handler().handle().expect("Error handling IRQ");
+            }
+        }
+
+        // Signal completion of handling.
+        self.gicc.mark_completed(irq, ic);
+    }
+
+    fn print_handler(&self) {
+        self.handler_table.lock(|table| {
+            for (i, opt) in table.iter().enumerate() {
+                if let Some(handler) = opt {
+                    info!("            {: >3}. {}", i, handler.name());
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,113 @@
+//! GICv2 Driver - ARM Generic Interrupt Controller v2, CPU Interface.
+//!
+//! All registers of the CPU interface are banked, so every core only sees its own copy.
+
+use crate::{bsp::device_driver::common::MMIODerefWrapper, exception};
+use tock_registers::{
+    interfaces::{Readable, Writeable},
+    register_bitfields, register_structs,
+    registers::{InMemoryRegister, ReadWrite},
+};
+
+register_bitfields! {
+    u32,
+
+    /// CPU Interface Control Register
+    CTLR [
+        Enable OFFSET(0) NUMBITS(1) []
+    ],
+
+    /// Interrupt Priority Mask Register
+    PMR [
+        Priority OFFSET(0) NUMBITS(8) []
+    ],
+
+    /// Interrupt Acknowledge Register
+    IAR [
+        CPUID OFFSET(10) NUMBITS(3) [],
+        InterruptID OFFSET(0) NUMBITS(10) []
+    ],
+
+    /// End of Interrupt Register
+    EOIR [
+        CPUID OFFSET(10) NUMBITS(3) [],
+        EOIINTID OFFSET(0) NUMBITS(10) []
+    ]
+}
+
+register_structs! {
+    #[allow(non_snake_case)]
+    pub RegisterBlock {
+        (0x000 => CTLR: ReadWrite<u32, CTLR::Register>),
+        (0x004 => PMR: ReadWrite<u32, PMR::Register>),
+        (0x008 => _reserved1),
+        (0x00C => IAR: ReadWrite<u32, IAR::Register>),
+        (0x010 => EOIR: ReadWrite<u32, EOIR::Register>),
+        (0x014 => @END),
+    }
+}
+
+type Registers = MMIODerefWrapper<RegisterBlock>;
+
+/// An acknowledged IRQ, which has to be handed back to `mark_completed` once handled.
+///
+/// For SGIs, the IAR also holds the id of the requesting core, which must be written back as is.
+pub struct AcknowledgedIRQ(InMemoryRegister<u32, IAR::Register>);
+
+impl AcknowledgedIRQ {
+    pub fn number(&self) -> usize {
+        self.0.read(IAR::InterruptID) as usize
+    }
+}
+
+/// Representation of the GIC CPU interface.
+pub struct GICC {
+    registers: Registers,
+}
+
+impl GICC {
+    pub const unsafe fn new(mmio_start_addr: usize) -> Self {
+        Self {
+            registers: Registers::new(mmio_start_addr),
+        }
+    }
+
+    /// Accept interrupts of any priority.
+    ///
+    /// Quoting the GICv2 Architecture Specification:
+    ///
+    ///   "Writing 255 to the GICC_PMR always sets it to the largest supported priority field
+    ///    value."
+    pub fn priority_accept_all(&self) {
+        self.registers.PMR.write(PMR::Priority.val(255));
+    }
+
+    /// Enable the interface - start accepting IRQs.
+    pub fn enable(&self) {
+        self.registers.CTLR.write(CTLR::Enable::SET);
+    }
+
+    /// Acknowledge the highest-priority pending IRQ.
+    ///
+    /// Can only be called from IRQ context, which is ensured by taking an `IRQContext` token.
+    pub fn acknowledge<'irq_context>(
+        &self,
+        _ic: &exception::asynchronous::IRQContext<'irq_context>,
+    ) -> AcknowledgedIRQ {
+        AcknowledgedIRQ(InMemoryRegister::new(self.registers.IAR.get()))
+    }
+
+    /// Complete handling of the currently active IRQ.
+    ///
+    /// Can only be called from IRQ context, which is ensured by taking an `IRQContext` token.
+    pub fn mark_completed<'irq_context>(
+        &self,
+        irq: AcknowledgedIRQ,
+        _ic: &exception::asynchronous::IRQContext<'irq_context>,
+    ) {
+        self.registers.EOIR.write(
+            EOIR::CPUID.val(irq.0.read(IAR::CPUID))
+                + EOIR::EOIINTID.val(irq.0.read(IAR::InterruptID)),
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{
-    bsp::device_driver::common::MMIODerefWrapper, driver, synchronization::{interface::Mutex, NullLock}
+    bsp::device_driver::common::MMIODerefWrapper, driver, exception::asynchronous::IRQNumber,
+    synchronization::{interface::Mutex, SpinLock}
 };
 use tock_registers::{
     interfaces::{ReadWriteable, Writeable},
@@ -90,7 +91,7 @@ struct GPIOInner {
 }
 
 pub struct GPIO {
-    inner: NullLock<GPIOInner>,
+    inner: SpinLock<GPIOInner>,
 }
 
 impl GPIOInner {
@@ -131,7 +132,7 @@ impl GPIO {
     
     pub const unsafe fn new(mmio_start_addr: usize) -> Self {
         Self {
-            inner: NullLock::new(GPIOInner::new(mmio_start_addr)),
+            inner: SpinLock::new(GPIOInner::new(mmio_start_addr)),
         }
     }
     
@@ -142,6 +143,8 @@ impl GPIO {
 
 
 impl driver::interface::DeviceDriver for GPIO {
+    type IRQNumberType = IRQNumber;
+
     fn compatible(&self) -> &'static str {
         Self::COMPATIBLE
     }
@@ -0,0 +1,156 @@
+mod local_ic;
+mod peripheral_ic;
+
+use crate::{
+    bsp::device_driver::common::BoundedUsize,
+    driver,
+    exception::{self, asynchronous::IRQHandlerDescriptor},
+};
+use core::fmt;
+
+pub type LocalIRQ = BoundedUsize<{ InterruptController::MAX_LOCAL_IRQ_NUMBER }>;
+pub type PeripheralIRQ = BoundedUsize<{ InterruptController::MAX_PERIPHERAL_IRQ_NUMBER }>;
+
+/// Used for the associated type of trait [`exception::asynchronous::interface::IRQManager`].
+#[derive(Copy, Clone)]
+pub enum IRQNumber {
+    Local(LocalIRQ),
+    Peripheral(PeripheralIRQ),
+}
+
+/// Iterator over the set bits of a pending-IRQs bitmask.
+struct PendingIRQs {
+    bitmask: u64,
+}
+
+pub struct InterruptController {
+    local: local_ic::LocalIC,
+    periph: peripheral_ic::PeripheralIC,
+}
+
+impl PendingIRQs {
+    pub fn new(bitmask: u64) -> Self {
+        Self { bitmask }
+    }
+}
+
+impl Iterator for PendingIRQs {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bitmask == 0 {
+            return None;
+        }
+
+        let next = self.bitmask.trailing_zeros() as usize;
+        self.bitmask &= self.bitmask.wrapping_sub(1);
+        Some(next)
+    }
+}
+
+impl fmt::Display for IRQNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Local(number) => write!(f, "Local({})", number),
+            Self::Peripheral(number) => write!(f, "Peripheral({})", number),
+        }
+    }
+}
+
+impl InterruptController {
+    // Core-local sources 0..=11, see the "Core interrupt sources" register of the BCM2836 ARM
+    // local peripherals.
+    const MAX_LOCAL_IRQ_NUMBER: usize = 11;
+    const MAX_PERIPHERAL_IRQ_NUMBER: usize = 63;
+
+    pub const COMPATIBLE: &'static str = "BCM Interrupt Controller";
+
+    pub const unsafe fn new(local_mmio_start_addr: usize, periph_mmio_start_addr: usize) -> Self {
+        Self {
+            local: local_ic::LocalIC::new(local_mmio_start_addr),
+            periph: peripheral_ic::PeripheralIC::new(periph_mmio_start_addr),
+        }
+    }
+}
+
+impl driver::interface::DeviceDriver for InterruptController {
+    type IRQNumberType = IRQNumber;
+
+    fn compatible(&self) -> &'static str {
+        Self::COMPATIBLE
+    }
+}
+
+impl exception::asynchronous::interface::IRQManager for InterruptController {
+    type IRQNumberType = IRQNumber;
+
+    fn register_handler(
+        &self,
+        irq_handler_descriptor: IRQHandlerDescriptor<Self::IRQNumberType>,
+    ) -> Result<(), &'static str> {
+        match irq_handler_descriptor.number() {
+            IRQNumber::Local(lirq) => {
+                let local_descriptor = IRQHandlerDescriptor::new(
+                    lirq,
+                    irq_handler_descriptor.name(),
+                    irq_handler_descriptor.handler(),
+                );
+
+                self.local.register_handler(local_descriptor)
+            }
+            IRQNumber::Peripheral(pirq) => {
+                let periph_descriptor = IRQHandlerDescriptor::new(
+                    pirq,
+                    irq_handler_descriptor.name(),
+                    irq_handler_descriptor.handler(),
+                );
+
+                self.periph.register_handler(periph_descriptor)
+            }
+        }
+    }
+
+    fn enable(&self, irq: &Self::IRQNumberType) -> Result<(), &'static str> {
+        match irq {
+            IRQNumber::Local(lirq) => self.local.enable(lirq),
+            IRQNumber::Peripheral(pirq) => self.periph.enable(pirq),
+        }
+
+        Ok(())
+    }
+
+    fn disable(&self, irq: &Self::IRQNumberType) -> Result<(), &'static str> {
+        match irq {
+            IRQNumber::Local(lirq) => self.local.disable(lirq),
+            IRQNumber::Peripheral(pirq) => self.periph.disable(pirq),
+        }
+
+        Ok(())
+    }
+
+    fn send_ipi(&self, irq: &Self::IRQNumberType, target_core: usize) -> Result<(), &'static str> {
+        match irq {
+            IRQNumber::Local(lirq) => self.local.send_ipi(lirq, target_core),
+            IRQNumber::Peripheral(_) => Err("Peripheral IRQs can not be sent as IPIs"),
+        }
+    }
+
+    fn handle_pending_irqs<'irq_context>(
+        &'irq_context self,
+        ic: &exception::asynchronous::IRQContext<'irq_context>,
+    ) {
+        // Peripheral IRQs arrive through the local controller as the GPU interrupt.
+        for irq_number in self.local.pending_irqs() {
+            if irq_number == local_ic::LocalIC::GPU_IRQ_NUMBER {
+                self.periph.handle_pending_irqs(ic);
+            } else {
+                self.local.handle_irq(irq_number, ic);
+            }
+        }
+    }
+
+    fn print_handler(&self) {
+        self.local.print_handler();
+        self.periph.print_handler();
+    }
+}
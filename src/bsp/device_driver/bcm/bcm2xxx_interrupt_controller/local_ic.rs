@@ -0,0 +1,184 @@
+//! The per-core interrupt routing of the BCM2836 "ARM local peripherals", which the BCM2837
+//! inherited. Core-local sources like the ARM generic timers end up here, as well as the GPU
+//! interrupt that signals pending peripheral IRQs, and the per-core mailboxes used for IPIs.
+
+use super::{LocalIRQ, PendingIRQs};
+use crate::{
+    bsp::device_driver::common::MMIODerefWrapper,
+    cpu,
+    exception, info,
+    synchronization::{interface::Mutex, IRQSafeSpinLock},
+};
+use aarch64_cpu::asm::barrier;
+use tock_registers::{
+    interfaces::{Readable, Writeable},
+    register_structs,
+    registers::{ReadOnly, ReadWrite, WriteOnly},
+};
+
+register_structs! {
+    #[allow(non_snake_case)]
+    RegisterBlock {
+        (0x00 => _reserved1),
+        (0x40 => CORE_TIMER_INTERRUPT_CONTROL: [ReadWrite<u32>; 4]),
+        (0x50 => CORE_MAILBOX_INTERRUPT_CONTROL: [ReadWrite<u32>; 4]),
+        (0x60 => CORE_IRQ_SOURCE: [ReadOnly<u32>; 4]),
+        (0x70 => _reserved2),
+        // Four mailboxes per core, indexed by `4 * core + mailbox`.
+        (0x80 => CORE_MAILBOX_WRITE_SET: [WriteOnly<u32>; 16]),
+        (0xC0 => CORE_MAILBOX_READ_CLEAR: [ReadWrite<u32>; 16]),
+        (0x100 => @END),
+    }
+}
+
+type Registers = MMIODerefWrapper<RegisterBlock>;
+
+type HandlerTable =
+    [Option<exception::asynchronous::IRQHandlerDescriptor<LocalIRQ>>; LocalIRQ::MAX_INCLUSIVE + 1];
+
+/// Representation of the core-local interrupt controller.
+pub struct LocalIC {
+    registers: IRQSafeSpinLock<Registers>,
+
+    /// Stores registered IRQ handlers.
+    handler_table: IRQSafeSpinLock<HandlerTable>,
+}
+
+impl LocalIC {
+    /// The four ARM generic timer interrupts are the IRQ sources 0 to 3, followed by the four
+    /// mailboxes of the executing core as sources 4 to 7.
+    const FIRST_MAILBOX_IRQ_NUMBER: usize = 4;
+    const MAX_MAILBOX_IRQ_NUMBER: usize = 7;
+
+    /// IRQ source bit that signals a pending peripheral IRQ.
+    pub const GPU_IRQ_NUMBER: usize = 8;
+
+    pub const unsafe fn new(mmio_start_addr: usize) -> Self {
+        Self {
+            registers: IRQSafeSpinLock::new(Registers::new(mmio_start_addr)),
+            handler_table: IRQSafeSpinLock::new([None; LocalIRQ::MAX_INCLUSIVE + 1]),
+        }
+    }
+
+    /// Index of the banked registers that belong to the executing core.
+    fn core_index() -> usize {
+        cpu::smp::core_id()
+    }
+
+    pub fn register_handler(
+        &self,
+        descriptor: exception::asynchronous::IRQHandlerDescriptor<LocalIRQ>,
+    ) -> Result<(), &'static str> {
+        let irq_number = descriptor.number().get();
+        if irq_number > Self::MAX_MAILBOX_IRQ_NUMBER {
+            return Err("Only the local timer and mailbox IRQs are supported yet");
+        }
+
+        self.handler_table.lock(|table| {
+            if table[irq_number].is_some() {
+                return Err("IRQ handler already registered");
+            }
+
+            table[irq_number] = Some(descriptor);
+
+            Ok(())
+        })
+    }
+
+    /// The mailbox behind a local IRQ number, if it is a mailbox IRQ.
+    fn mailbox(irq_number: usize) -> Option<usize> {
+        (Self::FIRST_MAILBOX_IRQ_NUMBER..=Self::MAX_MAILBOX_IRQ_NUMBER)
+            .contains(&irq_number)
+            .then(|| irq_number - Self::FIRST_MAILBOX_IRQ_NUMBER)
+    }
+
+    /// The executing core's interrupt control register for `irq`, and the bit that enables it.
+    fn control_reg_and_bit<'a>(
+        regs: &'a Registers,
+        irq: &LocalIRQ,
+    ) -> (&'a ReadWrite<u32>, u32) {
+        let core = Self::core_index();
+
+        match Self::mailbox(irq.get()) {
+            Some(mailbox) => (&regs.CORE_MAILBOX_INTERRUPT_CONTROL[core], 1 << mailbox),
+            None => (&regs.CORE_TIMER_INTERRUPT_CONTROL[core], 1 << irq.get()),
+        }
+    }
+
+    pub fn enable(&self, irq: &LocalIRQ) {
+        self.registers.lock(|regs| {
+            let (reg, bit) = Self::control_reg_and_bit(regs, irq);
+            reg.set(reg.get() | bit);
+        });
+    }
+
+    pub fn disable(&self, irq: &LocalIRQ) {
+        self.registers.lock(|regs| {
+            let (reg, bit) = Self::control_reg_and_bit(regs, irq);
+            reg.set(reg.get() & !bit);
+        });
+    }
+
+    /// Raise the mailbox IRQ `irq` on `target_core`.
+    pub fn send_ipi(&self, irq: &LocalIRQ, target_core: usize) -> Result<(), &'static str> {
+        let mailbox = Self::mailbox(irq.get()).ok_or("Not a mailbox IRQ")?;
+        if target_core >= 4 {
+            return Err("No such core");
+        }
+
+        // Make prior memory writes visible to the target before the IRQ arrives.
+        barrier::dsb(barrier::SY);
+
+        self.registers
+            .lock(|regs| regs.CORE_MAILBOX_WRITE_SET[4 * target_core + mailbox].set(1));
+
+        Ok(())
+    }
+
+    /// Query the list of pending local IRQs of the executing core.
+    pub fn pending_irqs(&self) -> PendingIRQs {
+        let pending = self
+            .registers
+            .lock(|regs| regs.CORE_IRQ_SOURCE[Self::core_index()].get());
+
+        PendingIRQs::new(u64::from(pending))
+    }
+
+    /// Dispatch a pending local IRQ other than the GPU one.
+    pub fn handle_irq<'irq_context>(
+        &'irq_context self,
+        irq_number: usize,
+        _ic: &exception::asynchronous::IRQContext<'irq_context>,
+    ) {
+        // A mailbox IRQ stays asserted until the mailbox is cleared.
+        if let Some(mailbox) = Self::mailbox(irq_number) {
+            self.registers.lock(|regs| {
+                regs.CORE_MAILBOX_READ_CLEAR[4 * Self::core_index() + mailbox].set(u32::MAX)
+            });
+        }
+
+        // Don't hold the lock while the handler runs, other cores might need the table.
+        match self
+            .handler_table
+            .lock(|table| table.get(irq_number).copied().flatten())
+        {
+            None => panic!("No handler registered for local IRQ {}", irq_number),
+            Some(descriptor) => {
+                // Call the IRQ handler. Panics on failure.
+                descriptor.handler().handle().expect("Error handling IRQ");
+            }
+        }
+    }
+
+    pub fn print_handler(&self) {
+        info!("      Local handler:");
+
+        self.handler_table.lock(|table| {
+            for (i, opt) in table.iter().enumerate() {
+                if let Some(handler) = opt {
+                    info!("            {: >3}. {}", i, handler.name());
+                }
+            }
+        });
+    }
+}
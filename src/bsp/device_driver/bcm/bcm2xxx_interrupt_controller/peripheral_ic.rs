@@ -0,0 +1,154 @@
+use super::{PendingIRQs, PeripheralIRQ};
+use crate::{
+    bsp::device_driver::common::MMIODerefWrapper,
+    exception, info,
+    synchronization::{interface::Mutex, IRQSafeSpinLock},
+};
+use tock_registers::{
+    interfaces::{Readable, Writeable},
+    register_structs,
+    registers::{ReadOnly, WriteOnly},
+};
+
+register_structs! {
+    #[allow(non_snake_case)]
+    WORegisterBlock {
+        (0x00 => _reserved1),
+        (0x10 => ENABLE_1: WriteOnly<u32>),
+        (0x14 => ENABLE_2: WriteOnly<u32>),
+        (0x18 => _reserved2),
+        (0x1C => DISABLE_1: WriteOnly<u32>),
+        (0x20 => DISABLE_2: WriteOnly<u32>),
+        (0x24 => @END),
+    }
+}
+
+register_structs! {
+    #[allow(non_snake_case)]
+    RORegisterBlock {
+        (0x00 => _reserved1),
+        (0x04 => PENDING_1: ReadOnly<u32>),
+        (0x08 => PENDING_2: ReadOnly<u32>),
+        (0x0c => @END),
+    }
+}
+
+type WriteOnlyRegisters = MMIODerefWrapper<WORegisterBlock>;
+type ReadOnlyRegisters = MMIODerefWrapper<RORegisterBlock>;
+
+type HandlerTable = [Option<exception::asynchronous::IRQHandlerDescriptor<PeripheralIRQ>>;
+    PeripheralIRQ::MAX_INCLUSIVE + 1];
+
+/// Representation of the peripheral interrupt controller.
+pub struct PeripheralIC {
+    /// Access to write registers is guarded with a lock.
+    wo_registers: IRQSafeSpinLock<WriteOnlyRegisters>,
+
+    /// Register read access is unguarded.
+    ro_registers: ReadOnlyRegisters,
+
+    /// Stores registered IRQ handlers.
+    handler_table: IRQSafeSpinLock<HandlerTable>,
+}
+
+impl PeripheralIC {
+    pub const unsafe fn new(mmio_start_addr: usize) -> Self {
+        Self {
+            wo_registers: IRQSafeSpinLock::new(WriteOnlyRegisters::new(mmio_start_addr)),
+            ro_registers: ReadOnlyRegisters::new(mmio_start_addr),
+            handler_table: IRQSafeSpinLock::new([None; PeripheralIRQ::MAX_INCLUSIVE + 1]),
+        }
+    }
+
+    /// Query the list of pending IRQs.
+    fn pending_irqs(&self) -> PendingIRQs {
+        let pending_mask: u64 = (u64::from(self.ro_registers.PENDING_2.get()) << 32)
+            | u64::from(self.ro_registers.PENDING_1.get());
+
+        PendingIRQs::new(pending_mask)
+    }
+
+    pub fn register_handler(
+        &self,
+        descriptor: exception::asynchronous::IRQHandlerDescriptor<PeripheralIRQ>,
+    ) -> Result<(), &'static str> {
+        self.handler_table.lock(|table| {
+            let irq_number = descriptor.number().get();
+
+            if table[irq_number].is_some() {
+                return Err("IRQ handler already registered");
+            }
+
+            table[irq_number] = Some(descriptor);
+
+            Ok(())
+        })
+    }
+
+    pub fn enable(&self, irq: &PeripheralIRQ) {
+        self.wo_registers.lock(|regs| {
+            let (enable_reg, enable_bit) = Self::reg_and_bit(irq);
+            let enable_reg = if enable_reg == 1 {
+                &regs.ENABLE_1
+            } else {
+                &regs.ENABLE_2
+            };
+
+            // Writing a 1 to a bit will set the corresponding IRQ enable bit. All other IRQ enable
+            // bits are unaffected. So we don't need read and OR'ing here.
+            enable_reg.set(1 << enable_bit);
+        });
+    }
+
+    pub fn disable(&self, irq: &PeripheralIRQ) {
+        self.wo_registers.lock(|regs| {
+            let (disable_reg, disable_bit) = Self::reg_and_bit(irq);
+            let disable_reg = if disable_reg == 1 {
+                &regs.DISABLE_1
+            } else {
+                &regs.DISABLE_2
+            };
+
+            // Same as for enable(): only the bits written as 1 are affected.
+            disable_reg.set(1 << disable_bit);
+        });
+    }
+
+    /// Which of the two 32 bit registers (1 or 2) holds the IRQ, and at which bit.
+    fn reg_and_bit(irq: &PeripheralIRQ) -> (usize, usize) {
+        let irq = irq.get();
+        if irq <= 31 {
+            (1, irq)
+        } else {
+            (2, irq - 32)
+        }
+    }
+
+    pub fn handle_pending_irqs<'irq_context>(
+        &'irq_context self,
+        _ic: &exception::asynchronous::IRQContext<'irq_context>,
+    ) {
+        for irq_number in self.pending_irqs() {
+            // Don't hold the lock while the handler runs, other cores might need the table.
+            match self.handler_table.lock(|table| table[irq_number]) {
+                None => panic!("No handler registered for IRQ {}", irq_number),
+                Some(descriptor) => {
+                    // Call the IRQ handler. Panics on failure.
+                    descriptor.handler().handle().expect("Error handling IRQ");
+                }
+            }
+        }
+    }
+
+    pub fn print_handler(&self) {
+        info!("      Peripheral handler:");
+
+        self.handler_table.lock(|table| {
+            for (i, opt) in table.iter().enumerate() {
+                if let Some(handler) = opt {
+                    info!("            {: >3}. {}", i, handler.name());
+                }
+            }
+        });
+    }
+}
@@ -1,5 +1,10 @@
 use crate::{
-    bsp::device_driver::common::MMIODerefWrapper, console, cpu, driver, synchronization::{self, NullLock}
+    bsp::device_driver::common::MMIODerefWrapper, console, cpu, driver,
+    executor::WakerSet,
+    exception::{self, asynchronous::IRQNumber}, ring_buffer::RingBuffer,
+    synchronization::{self, IRQSafeSpinLock},
+    thread::WaitQueue,
+    workqueue,
 };
 use core::fmt;
 use tock_registers::{
@@ -55,6 +60,42 @@ register_bitfields! {
         ],
     ],
     
+    /// Interrupt FIFO Level Select Register.
+    IFLS [
+        /// Receive interrupt FIFO level select.
+        RXIFLSEL OFFSET(3) NUMBITS(5) [
+            OneEigth = 0b000,
+            OneQuarter = 0b001,
+            OneHalf = 0b010,
+            ThreeQuarters = 0b011,
+            SevenEights = 0b100,
+        ],
+    ],
+
+    /// Interrupt Mask Set/Clear Register.
+    IMSC [
+        /// Receive timeout interrupt mask.
+        RTIM OFFSET(6) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1,
+        ],
+
+        /// Receive interrupt mask.
+        RXIM OFFSET(4) NUMBITS(1) [
+            Disabled = 0,
+            Enabled = 1,
+        ],
+    ],
+
+    /// Masked Interrupt Status Register.
+    MIS [
+        /// Receive timeout masked interrupt status.
+        RTMIS OFFSET(6) NUMBITS(1) [],
+
+        /// Receive masked interrupt status.
+        RXMIS OFFSET(4) NUMBITS(1) [],
+    ],
+
     ICR [
         ALL OFFSET(0) NUMBITS(11) [],
     ],
@@ -71,7 +112,10 @@ register_structs! {
         (0x28 => FBRD: WriteOnly<u32, FBRD::Register>),
         (0x2C => LCR_H: WriteOnly<u32, LCR_H::Register>),
         (0x30 => CR: WriteOnly<u32, CR::Register>),
-        (0x34 => _reserved3),
+        (0x34 => IFLS: ReadWrite<u32, IFLS::Register>),
+        (0x38 => IMSC: ReadWrite<u32, IMSC::Register>),
+        (0x3C => _reserved3),
+        (0x40 => MIS: ReadOnly<u32, MIS::Register>),
         (0x44 => ICR: WriteOnly<u32, ICR::Register>),
         (0x48 => @END),
     }
@@ -79,6 +123,9 @@ register_structs! {
 
 type Registers = MMIODerefWrapper<RegisterBlock>;
 
+/// Size of the software receive buffer that the IRQ handler drains the HW FIFO into.
+const RX_BUFFER_SIZE: usize = 256;
+
 #[derive(PartialEq)]
 enum BlockingMode {
     Blocking,
@@ -87,20 +134,28 @@ enum BlockingMode {
 
 struct PL011UartInner {
     registers: Registers,
+    rx_buffer: RingBuffer<u8, RX_BUFFER_SIZE>,
     chars_written: usize,
     chars_read: usize,
+    rx_overflows: usize,
 }
 
 pub struct PL011Uart {
-    inner: NullLock<PL011UartInner>,
+    inner: IRQSafeSpinLock<PL011UartInner>,
+
+    /// Readers waiting for received characters, woken up by the IRQ handler.
+    rx_wait: WaitQueue,
+    rx_wakers: WakerSet,
 }
 
 impl PL011UartInner {
     pub const unsafe fn new(base_addr: usize) -> Self {
         Self {
             registers: MMIODerefWrapper::new(base_addr),
+            rx_buffer: RingBuffer::new(),
             chars_written: 0,
             chars_read: 0,
+            rx_overflows: 0,
         }
     }
     
@@ -132,6 +187,12 @@ impl PL011UartInner {
         self.registers.IBRD.write(IBRD::BAUD_DIVINT.val(3));
         self.registers.FBRD.write(FBRD::BAUD_DIVFRAC.val(16));
         self.registers.LCR_H.write(LCR_H::WLEN::EightBit + LCR_H::FEN::FifosEnabled);
+
+        // Raise the RX interrupt as soon as the FIFO is 1/8 full, and the RX timeout interrupt if
+        // fewer characters than that sit in the FIFO for a while.
+        self.registers.IFLS.write(IFLS::RXIFLSEL::OneEigth);
+        self.registers.IMSC.write(IMSC::RXIM::Enabled + IMSC::RTIM::Enabled);
+
         self.registers.CR.write(CR::UARTEN::Enabled + CR::TXE::Enabled + CR::RXE::Enabled);
     }
     
@@ -151,17 +212,26 @@ impl PL011UartInner {
         }
     }
     
-    fn read_char_converting(&mut self, blocking_mode: BlockingMode) -> Option<char> {
-        if self.registers.FR.matches_all(FR::RXFE::SET) {
-            if blocking_mode == BlockingMode::NonBlocking {
-                return None;
-            }
-            while self.registers.FR.matches_all(FR::RXFE::SET) {
-                cpu::nop();
+    /// Move everything from the HW FIFO into the receive buffer.
+    ///
+    /// Characters that do not fit anymore are dropped and counted as overflows.
+    fn drain_rx_fifo(&mut self) {
+        while !self.registers.FR.matches_all(FR::RXFE::SET) {
+            let c = self.registers.DR.get() as u8;
+            if self.rx_buffer.push(c).is_err() {
+                self.rx_overflows += 1;
             }
         }
-        
-        let mut ret = self.registers.DR.get() as u8 as char;
+    }
+
+    fn read_char_converting(&mut self) -> Option<char> {
+        // Normally the IRQ handler keeps the buffer filled. Also drain here, so that reading works
+        // while IRQs are still masked, e.g. during early boot.
+        if self.rx_buffer.is_empty() {
+            self.drain_rx_fifo();
+        }
+
+        let mut ret = self.rx_buffer.pop()? as char;
         if ret == '\r' {
             ret = '\n';
         }
@@ -169,6 +239,26 @@ impl PL011UartInner {
         self.chars_read += 1;
         Some(ret)
     }
+
+    fn clear_rx(&mut self) {
+        self.drain_rx_fifo();
+        self.rx_buffer.clear();
+    }
+
+    /// Returns true if characters were received.
+    fn handle_irq(&mut self) -> bool {
+        let pending = self.registers.MIS.extract();
+
+        // Ack all pending interrupts.
+        self.registers.ICR.write(ICR::ALL::CLEAR);
+
+        let received = pending.matches_any(MIS::RXMIS::SET + MIS::RTMIS::SET);
+        if received {
+            self.drain_rx_fifo();
+        }
+
+        received
+    }
 }
 
 impl fmt::Write for PL011UartInner {
@@ -185,12 +275,41 @@ impl PL011Uart {
     pub const COMPATIBLE: &'static str = "BCM PL011 UART";
     pub const unsafe fn new(mmio_start_addr: usize) -> Self {
         Self {
-            inner: NullLock::new(PL011UartInner::new(mmio_start_addr))
+            inner: IRQSafeSpinLock::new(PL011UartInner::new(mmio_start_addr)),
+            rx_wait: WaitQueue::new(),
+            rx_wakers: WakerSet::new(),
+        }
+    }
+
+    fn wake_readers(&self) {
+        self.rx_wait.wake_all();
+        self.rx_wakers.wake_all();
+    }
+
+    /// Bottom half of the RX IRQ, `context` is a `&'static PL011Uart`.
+    fn wake_readers_work(context: usize) {
+        let uart = unsafe { &*(context as *const Self) };
+        uart.wake_readers();
+    }
+
+    fn read_char_converting(&self, blocking_mode: BlockingMode) -> Option<char> {
+        if blocking_mode == BlockingMode::NonBlocking {
+            return self.inner.lock(|inner| inner.read_char_converting());
         }
+
+        let mut ret = None;
+        self.rx_wait.wait_until(|| {
+            ret = self.inner.lock(|inner| inner.read_char_converting());
+            ret.is_some()
+        });
+
+        ret
     }
 }
 
 impl driver::interface::DeviceDriver for PL011Uart {
+    type IRQNumberType = IRQNumber;
+
     fn compatible(&self) -> &'static str {
         PL011Uart::COMPATIBLE
     }
@@ -200,6 +319,37 @@ impl driver::interface::DeviceDriver for PL011Uart {
         });
         Ok(())
     }
+
+    fn register_and_enable_irq_handler(
+        &'static self,
+        irq_number: &Self::IRQNumberType,
+    ) -> Result<(), &'static str> {
+        use exception::asynchronous::{irq_manager, IRQHandlerDescriptor};
+
+        let descriptor = IRQHandlerDescriptor::new(*irq_number, Self::COMPATIBLE, self);
+
+        irq_manager().register_handler(descriptor)?;
+        irq_manager().enable(irq_number)?;
+
+        Ok(())
+    }
+}
+
+impl exception::asynchronous::interface::IRQHandler for PL011Uart {
+    fn handle(&self) -> Result<(), &'static str> {
+        if !self.inner.lock(|inner| inner.handle_irq()) {
+            return Ok(());
+        }
+
+        // The handler is only registered through `register_and_enable_irq_handler`, which takes a
+        // `&'static self`.
+        let context = self as *const Self as usize;
+        if workqueue::system().queue(Self::wake_readers_work, context).is_err() {
+            self.wake_readers();
+        }
+
+        Ok(())
+    }
 }
 
 impl console::interface::Write for PL011Uart {
@@ -218,10 +368,16 @@ impl console::interface::Write for PL011Uart {
 
 impl console::interface::Read for PL011Uart {
     fn read_char(&self) -> char {
-        self.inner.lock(|inner| inner.read_char_converting(BlockingMode::Blocking).unwrap())
+        self.read_char_converting(BlockingMode::Blocking).unwrap()
+    }
+    fn try_read_char(&self) -> Option<char> {
+        self.read_char_converting(BlockingMode::NonBlocking)
+    }
+    fn register_rx_waker(&self, waker: &core::task::Waker) {
+        self.rx_wakers.register(waker);
     }
     fn clear_rx(&self) {
-        while self.inner.lock(|inner| inner.read_char_converting(BlockingMode::NonBlocking).is_some()) {}
+        self.inner.lock(|inner| inner.clear_rx());
     }
 }
 
@@ -233,6 +389,10 @@ impl console::interface::Statistics for PL011Uart {
     fn chars_read(&self) -> usize {
         self.inner.lock(|inner| inner.chars_read)
     }
+
+    fn rx_overflows(&self) -> usize {
+        self.inner.lock(|inner| inner.rx_overflows)
+    }
 }
 
 impl console::interface::All for PL011Uart {}
@@ -1,4 +1,4 @@
-use core::{marker::PhantomData, ops};
+use core::{fmt, marker::PhantomData, ops};
 
 pub struct MMIODerefWrapper<T> {
     start_addr: usize,
@@ -20,3 +20,28 @@ impl<T> ops::Deref for MMIODerefWrapper<T> {
         unsafe {&*(self.start_addr as *const _)}
     }
 }
+
+/// A wrapper type for usize with integrated range bound check.
+#[derive(Copy, Clone)]
+pub struct BoundedUsize<const MAX_INCLUSIVE: usize>(usize);
+
+impl<const MAX_INCLUSIVE: usize> BoundedUsize<{ MAX_INCLUSIVE }> {
+    pub const MAX_INCLUSIVE: usize = MAX_INCLUSIVE;
+
+    /// Panics if `number` is out of range.
+    pub const fn new(number: usize) -> Self {
+        assert!(number <= MAX_INCLUSIVE);
+
+        Self(number)
+    }
+
+    pub const fn get(self) -> usize {
+        self.0
+    }
+}
+
+impl<const MAX_INCLUSIVE: usize> fmt::Display for BoundedUsize<{ MAX_INCLUSIVE }> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
@@ -1,6 +1,7 @@
 pub mod console;
 pub mod cpu;
 pub mod driver;
+pub mod exception;
 pub mod memory;
 
 pub fn board_name() -> &'static str {
@@ -8,4 +9,9 @@ pub fn board_name() -> &'static str {
     {
         "Raspberry Pi 3"
     }
+
+    #[cfg(feature = "bsp_rpi4")]
+    {
+        "Raspberry Pi 4"
+    }
 }
@@ -13,7 +13,7 @@ impl QEMUOutputInner {
     
     pub fn write_char(&mut self, c: char) {
         unsafe {
-            core::ptr::write_volatile(0x3F20_1000 as *mut u8, c as u8)
+            core::ptr::write_volatile(super::memory::phys_to_virt(0x3F20_1000) as *mut u8, c as u8)
         }
         self.chars_written += 1;
     }
@@ -79,3 +79,25 @@ static QEMU_OUTPUT: QEMUOutput = QEMUOutput::new();
 pub fn console() -> &'static dyn console::interface::All {
     &super::driver::PL011_UART
 }
+
+/// Write `msg` to the PL011 UART at its physical address, without its driver.
+///
+/// For the boot code, to report a failure while the MMU is still off.
+///
+/// # Safety
+///
+/// - The MMU must be off.
+/// - Must not depend on the kernel's link address.
+#[inline(always)]
+pub unsafe fn early_write(msg: &[u8]) {
+    const DR: usize = 0x00;
+    const FR: usize = 0x18;
+    const FR_TXFF: u32 = 1 << 5;
+
+    let base = super::memory::map::mmio::PL011_UART_START;
+
+    for &byte in msg {
+        while core::ptr::read_volatile((base + FR) as *const u32) & FR_TXFF != 0 {}
+        core::ptr::write_volatile((base + DR) as *mut u32, byte as u32);
+    }
+}
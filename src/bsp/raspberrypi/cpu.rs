@@ -1,3 +1,43 @@
+use super::memory::{phys_to_virt, virt_to_phys};
+use crate::{cpu::smp, time, warn};
+use core::time::Duration;
+
 #[no_mangle]
 #[link_section = ".text._start_arguments"]
 pub static BOOT_CORE_ID: u64 = 0;
+
+pub const NUM_CORES: usize = 4;
+
+/// Base of the spin table polled by the secondary cores. Core n waits for its entry address in
+/// the 64 bit slot at `SPIN_TABLE_BASE + 8 * n`.
+pub const SPIN_TABLE_BASE: usize = 0xD8;
+
+/// How long to wait for a released core to come online.
+const SECONDARY_CORE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Release all secondary cores into the kernel and wait until they are online.
+///
+/// # Safety
+///
+/// - Must only be called once, by the boot core.
+pub unsafe fn start_secondary_cores() {
+    // The cores are released with the MMU off, so they need the physical address.
+    let entry = virt_to_phys(smp::secondary_entry_addr()) as u64;
+
+    for core_id in (0..NUM_CORES).filter(|&i| i as u64 != BOOT_CORE_ID) {
+        let slot = phys_to_virt(SPIN_TABLE_BASE + 8 * core_id) as *mut u64;
+        smp::write_spin_table_entry(slot, entry);
+    }
+    smp::wake_secondary_cores();
+
+    let deadline = time::time_manager().uptime() + SECONDARY_CORE_TIMEOUT;
+    while smp::num_online_cores() < NUM_CORES && time::time_manager().uptime() < deadline {}
+
+    if smp::num_online_cores() < NUM_CORES {
+        warn!(
+            "Only {} of {} cores came online",
+            smp::num_online_cores(),
+            NUM_CORES
+        );
+    }
+}
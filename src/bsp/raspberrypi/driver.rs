@@ -1,9 +1,22 @@
-use super::memory::map::mmio ;
-use crate::{bsp::device_driver, console, driver as generic_driver};
-use core::sync::atomic::{AtomicBool, Ordering};
+use super::{
+    exception::asynchronous::irq_map,
+    memory::{map::mmio, phys_to_virt},
+};
+use crate::{
+    bsp::device_driver, console, cpu, driver as generic_driver, exception,
+    synchronization::InitOnce, time,
+};
 
-pub static PL011_UART: device_driver::PL011Uart = unsafe { device_driver::PL011Uart::new(mmio::PL011_UART_START) };
-static GPIO: device_driver::GPIO = unsafe { device_driver::GPIO::new(mmio::GPIO_START)};
+pub static PL011_UART: device_driver::PL011Uart = unsafe { device_driver::PL011Uart::new(phys_to_virt(mmio::PL011_UART_START)) };
+static GPIO: device_driver::GPIO = unsafe { device_driver::GPIO::new(phys_to_virt(mmio::GPIO_START))};
+
+#[cfg(feature = "bsp_rpi3")]
+static INTERRUPT_CONTROLLER: device_driver::InterruptController =
+    unsafe { device_driver::InterruptController::new(phys_to_virt(mmio::LOCAL_IC_START), phys_to_virt(mmio::PERIPHERAL_IC_START)) };
+
+#[cfg(feature = "bsp_rpi4")]
+static INTERRUPT_CONTROLLER: device_driver::GICv2 =
+    unsafe { device_driver::GICv2::new(phys_to_virt(mmio::GICD_START), phys_to_virt(mmio::GICC_START)) };
 
 fn post_init_uart() -> Result<(), &'static str> {
     console::register_console(&PL011_UART);
@@ -15,11 +28,16 @@ fn post_init_gpio() -> Result<(), &'static str> {
     Ok(())
 }
 
+fn post_init_interrupt_controller() -> Result<(), &'static str> {
+    exception::asynchronous::register_irq_manager(&INTERRUPT_CONTROLLER);
+    Ok(())
+}
 
 fn driver_uart() -> Result<(), &'static str> {
     let d = generic_driver::DeviceDriverDescriptor::new(
         &PL011_UART,
         Some(post_init_uart),
+        Some(irq_map::PL011_UART),
     );
     generic_driver::driver_manager().register_driver(d);
     Ok(())
@@ -29,18 +47,51 @@ fn driver_gpio() -> Result<(), &'static str> {
     let d = generic_driver::DeviceDriverDescriptor::new(
         &GPIO,
         Some(post_init_gpio),
+        None,
+    );
+    generic_driver::driver_manager().register_driver(d);
+    Ok(())
+}
+
+fn driver_arch_timer() -> Result<(), &'static str> {
+    let d = generic_driver::DeviceDriverDescriptor::new(
+        time::time_manager(),
+        None,
+        Some(irq_map::ARCH_TIMER),
+    );
+    generic_driver::driver_manager().register_driver(d);
+    Ok(())
+}
+
+fn driver_cross_call() -> Result<(), &'static str> {
+    let d = generic_driver::DeviceDriverDescriptor::new(
+        cpu::smp::cross_call_manager(),
+        None,
+        Some(irq_map::IPI),
+    );
+    generic_driver::driver_manager().register_driver(d);
+    Ok(())
+}
+
+fn driver_interrupt_controller() -> Result<(), &'static str> {
+    let d = generic_driver::DeviceDriverDescriptor::new(
+        &INTERRUPT_CONTROLLER,
+        Some(post_init_interrupt_controller),
+        None,
     );
     generic_driver::driver_manager().register_driver(d);
     Ok(())
 }
 
 pub unsafe fn init() -> Result<(), &'static str> {
-    static INIT_DONE: AtomicBool = AtomicBool::new(false);
-    if INIT_DONE.load(Ordering::Relaxed) {
+    static INIT_DONE: InitOnce<()> = InitOnce::new();
+    if INIT_DONE.set(()).is_err() {
         return Err("Init already done");
     }
     driver_uart()?;
     driver_gpio()?;
-    INIT_DONE.store(true, Ordering::Relaxed);
+    driver_interrupt_controller()?;
+    driver_arch_timer()?;
+    driver_cross_call()?;
     Ok(())
 }
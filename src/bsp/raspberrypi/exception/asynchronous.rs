@@ -0,0 +1,28 @@
+use crate::bsp::device_driver;
+
+/// Both boards re-export their interrupt controller's number type as `device_driver::IRQNumber`.
+pub type IRQNumber = device_driver::IRQNumber;
+
+#[cfg(feature = "bsp_rpi3")]
+pub(in crate::bsp) mod irq_map {
+    use super::device_driver::{IRQNumber, LocalIRQ, PeripheralIRQ};
+
+    /// CNTPNSIRQ, the non-secure EL1 physical timer.
+    pub const ARCH_TIMER: IRQNumber = IRQNumber::Local(LocalIRQ::new(1));
+    pub const PL011_UART: IRQNumber = IRQNumber::Peripheral(PeripheralIRQ::new(57));
+
+    /// Mailbox 0 of the executing core.
+    pub const IPI: IRQNumber = IRQNumber::Local(LocalIRQ::new(4));
+}
+
+#[cfg(feature = "bsp_rpi4")]
+pub(in crate::bsp) mod irq_map {
+    use super::device_driver::IRQNumber;
+
+    /// PPI 14, the non-secure EL1 physical timer.
+    pub const ARCH_TIMER: IRQNumber = IRQNumber::new(30);
+    pub const PL011_UART: IRQNumber = IRQNumber::new(153);
+
+    /// SGI 0.
+    pub const IPI: IRQNumber = IRQNumber::new(0);
+}
@@ -1,12 +1,128 @@
+pub mod mmu;
+
+mod layout;
+
+use crate::memory::mmu::{
+    AccessPermissions, AttributeFields, Bound, MemAttributes, RangeDescriptor,
+};
+use core::cell::UnsafeCell;
+
+// Symbols from the linker script.
+extern "Rust" {
+    static __boot_core_stack_start: UnsafeCell<()>;
+
+    static __code_start: UnsafeCell<()>;
+    static __code_end_exclusive: UnsafeCell<()>;
+
+    static __rodata_start: UnsafeCell<()>;
+    static __rodata_end_exclusive: UnsafeCell<()>;
+
+    static __data_start: UnsafeCell<()>;
+    static __data_end_exclusive: UnsafeCell<()>;
+}
+
 pub(super) mod map {
+    pub const END_INCLUSIVE: usize = super::layout::END_INCLUSIVE;
+
+    pub const KERNEL_VIRT_OFFSET: usize = super::layout::KERNEL_VIRT_OFFSET;
+
     pub const GPIO_OFFSET: usize = 0x0020_0000;
     pub const UART_OFFSET: usize = 0x0020_1000;
+
+    #[cfg(feature = "bsp_rpi3")]
+    pub const PERIPHERAL_IC_OFFSET: usize = 0x0000_B200;
     
     #[cfg(feature = "bsp_rpi3")]
     pub mod mmio {
         use super::*;
-        pub const START: usize = 0x3F00_0000;
+        use crate::bsp::memory::layout::rpi3;
+        pub const START: usize = rpi3::MMIO_START;
         pub const GPIO_START: usize = START + GPIO_OFFSET;
         pub const PL011_UART_START: usize = START + UART_OFFSET;
+        pub const PERIPHERAL_IC_START: usize = START + PERIPHERAL_IC_OFFSET;
+        pub const LOCAL_IC_START: usize = 0x4000_0000;
+        pub const END_INCLUSIVE: usize = rpi3::MMIO_END_INCLUSIVE;
     }
+
+    #[cfg(feature = "bsp_rpi4")]
+    pub mod mmio {
+        use super::*;
+        use crate::bsp::memory::layout::rpi4;
+        pub const START: usize = rpi4::MMIO_START;
+        pub const GPIO_START: usize = START + GPIO_OFFSET;
+        pub const PL011_UART_START: usize = START + UART_OFFSET;
+        pub const GICD_START: usize = 0xFF84_1000;
+        pub const GICC_START: usize = 0xFF84_2000;
+        pub const END_INCLUSIVE: usize = rpi4::MMIO_END_INCLUSIVE;
+    }
+}
+
+/// The kernel's virtual address of physical address `phys`.
+#[inline(always)]
+pub const fn phys_to_virt(phys: usize) -> usize {
+    phys + map::KERNEL_VIRT_OFFSET
+}
+
+/// The physical address the kernel's virtual address `virt` maps to.
+#[inline(always)]
+pub const fn virt_to_phys(virt: usize) -> usize {
+    virt - map::KERNEL_VIRT_OFFSET
+}
+
+/// The address of a linker symbol the layout refers to.
+fn symbol_addr(name: &str) -> Option<usize> {
+    let addr = match name {
+        "__boot_core_stack_start" => boot_core_stack_start(),
+        "__code_start" => code_start(),
+        "__code_end_exclusive" => code_end_exclusive(),
+        "__rodata_start" => rodata_start(),
+        "__rodata_end_exclusive" => rodata_end_exclusive(),
+        "__data_start" => data_start(),
+        "__data_end_exclusive" => data_end_exclusive(),
+        _ => return None,
+    };
+
+    Some(addr)
+}
+
+/// Start of the boot core's stack, which is the start of DRAM.
+#[inline(always)]
+fn boot_core_stack_start() -> usize {
+    unsafe { __boot_core_stack_start.get() as usize }
+}
+
+/// Start of the kernel's code, page aligned.
+#[inline(always)]
+fn code_start() -> usize {
+    unsafe { __code_start.get() as usize }
+}
+
+/// End of the kernel's code, page aligned.
+#[inline(always)]
+fn code_end_exclusive() -> usize {
+    unsafe { __code_end_exclusive.get() as usize }
+}
+
+/// Start of the kernel's read-only data, page aligned.
+#[inline(always)]
+fn rodata_start() -> usize {
+    unsafe { __rodata_start.get() as usize }
+}
+
+/// End of the kernel's read-only data, page aligned.
+#[inline(always)]
+fn rodata_end_exclusive() -> usize {
+    unsafe { __rodata_end_exclusive.get() as usize }
+}
+
+/// Start of the kernel's data, BSS and secondary core stacks, page aligned.
+#[inline(always)]
+fn data_start() -> usize {
+    unsafe { __data_start.get() as usize }
+}
+
+/// End of the kernel's data, BSS and secondary core stacks, page aligned.
+#[inline(always)]
+fn data_end_exclusive() -> usize {
+    unsafe { __data_end_exclusive.get() as usize }
 }
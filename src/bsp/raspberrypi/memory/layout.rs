@@ -0,0 +1,97 @@
+//! The kernel's virtual memory layout on the Raspberry Pi.
+//!
+//! `tools/translation_table_tool` computes the kernel's translation tables from this same file, so
+//! it holds plain data only. Both include it as a module next to their own `AttributeFields`,
+//! `Bound` and `RangeDescriptor`.
+
+use super::{AccessPermissions, AttributeFields, Bound, MemAttributes, RangeDescriptor};
+
+/// End of the 4 GiB physical address space.
+pub const END_INCLUSIVE: usize = 0xFFFF_FFFF;
+
+/// The kernel's virtual address of physical address 0. Must match `kernel.ld`.
+pub const KERNEL_VIRT_OFFSET: usize = 0xFFFF_FFFF_0000_0000;
+
+pub const NUM_RANGES: usize = 5;
+
+/// The kernel exports its board's `NAME` under this symbol, so the tool knows which layout to use.
+// Only the tool looks it up by name.
+#[allow(dead_code)]
+pub const BSP_SYMBOL: &str = "GOOSE_BSP";
+
+// The kernel only uses the MMIO range of its own board.
+#[allow(dead_code)]
+pub mod rpi3 {
+    pub const NAME: [u8; 4] = *b"rpi3";
+    pub const MMIO_START: usize = 0x3F00_0000;
+    pub const MMIO_END_INCLUSIVE: usize = 0x4000_FFFF;
+}
+
+#[allow(dead_code)]
+pub mod rpi4 {
+    pub const NAME: [u8; 4] = *b"rpi4";
+    pub const MMIO_START: usize = 0xFE00_0000;
+    pub const MMIO_END_INCLUSIVE: usize = 0xFF84_FFFF;
+}
+
+const CACHEABLE_RW_XN: AttributeFields = AttributeFields {
+    mem_attributes: MemAttributes::CacheableDRAM,
+    acc_perms: AccessPermissions::ReadWrite,
+    execute_never: true,
+};
+
+const CACHEABLE_RO_X: AttributeFields = AttributeFields {
+    mem_attributes: MemAttributes::CacheableDRAM,
+    acc_perms: AccessPermissions::ReadOnly,
+    execute_never: false,
+};
+
+const CACHEABLE_RO_XN: AttributeFields = AttributeFields {
+    mem_attributes: MemAttributes::CacheableDRAM,
+    acc_perms: AccessPermissions::ReadOnly,
+    execute_never: true,
+};
+
+const DEVICE_RW_XN: AttributeFields = AttributeFields {
+    mem_attributes: MemAttributes::Device,
+    acc_perms: AccessPermissions::ReadWrite,
+    execute_never: true,
+};
+
+/// The kernel's ranges on a board with the given MMIO range. Everything else, for example DRAM
+/// above the kernel, stays unmapped.
+pub const fn ranges(mmio_start: usize, mmio_end_inclusive: usize) -> [RangeDescriptor; NUM_RANGES] {
+    [
+        // Also holds the spin table the secondary cores are parked on.
+        RangeDescriptor {
+            name: "Boot core stack",
+            start: Bound::Symbol("__boot_core_stack_start"),
+            end_exclusive: Bound::Symbol("__code_start"),
+            attribute_fields: CACHEABLE_RW_XN,
+        },
+        RangeDescriptor {
+            name: "Kernel code",
+            start: Bound::Symbol("__code_start"),
+            end_exclusive: Bound::Symbol("__code_end_exclusive"),
+            attribute_fields: CACHEABLE_RO_X,
+        },
+        RangeDescriptor {
+            name: "Kernel RO data",
+            start: Bound::Symbol("__rodata_start"),
+            end_exclusive: Bound::Symbol("__rodata_end_exclusive"),
+            attribute_fields: CACHEABLE_RO_XN,
+        },
+        RangeDescriptor {
+            name: "Kernel data, BSS and stacks",
+            start: Bound::Symbol("__data_start"),
+            end_exclusive: Bound::Symbol("__data_end_exclusive"),
+            attribute_fields: CACHEABLE_RW_XN,
+        },
+        RangeDescriptor {
+            name: "Device MMIO",
+            start: Bound::Phys(mmio_start),
+            end_exclusive: Bound::Phys(mmio_end_inclusive + 1),
+            attribute_fields: DEVICE_RW_XN,
+        },
+    ]
+}
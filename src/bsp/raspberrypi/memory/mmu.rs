@@ -0,0 +1,36 @@
+//! BSP memory management unit.
+
+use super::{layout, map as memory_map};
+use crate::memory::mmu::*;
+
+/// The kernel's address space, covering the physical one up to and including the MMIO ranges.
+pub type KernelAddrSpace = AddressSpace<{ memory_map::END_INCLUSIVE + 1 }>;
+
+// The kernel's address space is the top of the virtual one, mapping the physical one linearly.
+const _: () = assert!(memory_map::KERNEL_VIRT_OFFSET == usize::MAX - KernelAddrSpace::SIZE + 1);
+
+/// The kernel's ranges, shared with `tools/translation_table_tool`.
+static LAYOUT: KernelVirtualLayout<{ layout::NUM_RANGES }> = KernelVirtualLayout::new(
+    memory_map::KERNEL_VIRT_OFFSET,
+    layout::ranges(memory_map::mmio::START, memory_map::mmio::END_INCLUSIVE),
+    super::symbol_addr,
+);
+
+/// Names the board, see `layout::BSP_SYMBOL`.
+#[no_mangle]
+#[used]
+static GOOSE_BSP: [u8; 4] = {
+    #[cfg(feature = "bsp_rpi3")]
+    {
+        layout::rpi3::NAME
+    }
+
+    #[cfg(feature = "bsp_rpi4")]
+    {
+        layout::rpi4::NAME
+    }
+};
+
+pub fn virt_mem_layout() -> &'static KernelVirtualLayout<{ layout::NUM_RANGES }> {
+    &LAYOUT
+}
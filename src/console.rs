@@ -1,9 +1,14 @@
 mod null_console;
 
-use crate::synchronization::{self, NullLock};
+use crate::synchronization::{self, RwLock};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
 
 pub mod interface {
-    use core::fmt;
+    use core::{fmt, task::Waker};
     
     pub trait Write {
         fn write_char(&self, c: char);
@@ -15,6 +20,12 @@ pub mod interface {
         fn read_char(&self) -> char {
             ' '
         }
+        /// Like `read_char`, but returns `None` instead of waiting if nothing was received.
+        fn try_read_char(&self) -> Option<char> {
+            None
+        }
+        /// Wake `waker` once a character was received.
+        fn register_rx_waker(&self, _waker: &Waker) {}
         fn clear_rx(&self);
     }
     
@@ -25,19 +36,54 @@ pub mod interface {
         fn chars_read(&self) -> usize {
             0
         }
+        /// Number of received characters that were dropped because the receive buffer was full.
+        fn rx_overflows(&self) -> usize {
+            0
+        }
     }
     
     pub trait All: Read + Write + Statistics {}
 }
 
-static CUR_CONSOLE: NullLock<&'static (dyn interface::All + Sync)> = NullLock::new(&null_console::NULL_CONSOLE);
+/// Returned by `read_char_async`.
+pub struct ReadChar<'a> {
+    console: &'a dyn interface::All,
+}
+
+impl dyn interface::All {
+    /// Wait for the next received character, like `read_char`, but without blocking the thread.
+    pub fn read_char_async(&self) -> ReadChar<'_> {
+        ReadChar { console: self }
+    }
+}
+
+impl Future for ReadChar<'_> {
+    type Output = char;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<char> {
+        if let Some(c) = self.console.try_read_char() {
+            return Poll::Ready(c);
+        }
+
+        self.console.register_rx_waker(cx.waker());
+
+        // A character might have arrived before the waker was registered.
+        match self.console.try_read_char() {
+            Some(c) => Poll::Ready(c),
+            None => Poll::Pending,
+        }
+    }
+}
+
+static CUR_CONSOLE: RwLock<&'static (dyn interface::All + Sync)> =
+    RwLock::new(&null_console::NULL_CONSOLE);
 
-use synchronization::interface::Mutex;
+use synchronization::interface::ReadWriteEx;
 
 pub fn register_console(console: &'static (dyn interface::All + Sync)) {
-    CUR_CONSOLE.lock(|c| *c = console);
+    CUR_CONSOLE.write(|c| *c = console);
 }
 
 pub fn console() -> &'static dyn interface::All {
-    CUR_CONSOLE.lock(|c| *c)
+    CUR_CONSOLE.read(|c| *c)
 }
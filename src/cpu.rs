@@ -8,7 +8,26 @@ mod arch_cpu;
 
 mod boot;
 
-pub use arch_cpu::{nop, wait_forever};
+pub mod per_cpu;
+pub mod preempt;
+pub mod smp;
+
+/// Kernel privilege levels.
+#[derive(Eq, PartialEq)]
+pub enum PrivilegeLevel {
+    User,
+    Kernel,
+    Hypervisor,
+    Unknown,
+}
+
+pub use arch_cpu::{
+    current_privilege_level, exec_with_irq_masked, exclusives_usable, local_irq_mask,
+    local_irq_unmask, nop, wait_for_interrupt, wait_forever,
+};
+
+#[cfg(target_arch = "aarch64")]
+pub use arch_cpu::syndrome;
 
 #[cfg(feature = "bsp_rpi3")]
 pub use arch_cpu::spin_for_cycles;
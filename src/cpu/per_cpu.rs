@@ -0,0 +1,54 @@
+//! Per-core data.
+//!
+//! Every core owns one slot of a `PerCpu`, found through its core id (MPIDR affinity on aarch64,
+//! hart id on riscv64). Slots are padded to a cache line each, so that cores updating their own
+//! slot do not contend. There is no locking; slot types bring their own interior mutability,
+//! usually atomics, which also lets other cores read them for reporting.
+
+use crate::{bsp, cpu::smp};
+
+/// A value aligned and padded to a cache line.
+#[repr(align(64))]
+pub struct CachePadded<T>(T);
+
+pub struct PerCpu<T> {
+    slots: [CachePadded<T>; bsp::cpu::NUM_CORES],
+}
+
+/// Create a `PerCpu`, initializing every core's slot with a separate evaluation of the given
+/// constant expression.
+#[macro_export]
+macro_rules! per_cpu {
+    ($init:expr) => {
+        $crate::cpu::per_cpu::PerCpu::from_slots(
+            [const { $crate::cpu::per_cpu::CachePadded::new($init) };
+                $crate::bsp::cpu::NUM_CORES],
+        )
+    };
+}
+
+impl<T> CachePadded<T> {
+    pub const fn new(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> PerCpu<T> {
+    pub const fn from_slots(slots: [CachePadded<T>; bsp::cpu::NUM_CORES]) -> Self {
+        Self { slots }
+    }
+
+    /// The slot of the executing core.
+    pub fn local(&self) -> &T {
+        &self.slots[smp::core_id()].0
+    }
+
+    pub fn get(&self, core_id: usize) -> Option<&T> {
+        self.slots.get(core_id).map(|slot| &slot.0)
+    }
+
+    /// All slots, together with the id of the core owning them.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &T)> {
+        self.slots.iter().map(|slot| &slot.0).enumerate()
+    }
+}
@@ -0,0 +1,30 @@
+//! Per-core preemption count.
+//!
+//! Every `SpinLock` raises the count of the executing core while it's held. The scheduler does not
+//! preempt the current thread while the count is non-zero: another thread on this core spinning
+//! for a lock held by a preempted thread would otherwise wait for a full time slice, or be taken
+//! for recursive locking.
+//!
+//! The count is only changed by its own core, and IRQ handlers leave it as they found it, so plain
+//! loads and stores suffice. Those work before the MMU is enabled, too.
+
+use crate::{cpu::per_cpu::PerCpu, per_cpu};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+static PREEMPT_COUNT: PerCpu<AtomicUsize> = per_cpu!(AtomicUsize::new(0));
+
+/// Don't preempt the current thread until the matching `enable`.
+pub fn disable() {
+    let count = PREEMPT_COUNT.local();
+    count.store(count.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+}
+
+pub fn enable() {
+    let count = PREEMPT_COUNT.local();
+    count.store(count.load(Ordering::Relaxed) - 1, Ordering::Relaxed);
+}
+
+/// Whether the current thread of the executing core may be preempted.
+pub fn is_preemptible() -> bool {
+    PREEMPT_COUNT.local().load(Ordering::Relaxed) == 0
+}
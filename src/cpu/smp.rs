@@ -0,0 +1,58 @@
+#[cfg(target_arch = "aarch64")]
+#[path = "../_arch/aarch64/cpu/smp.rs"]
+mod arch_smp;
+
+#[cfg(target_arch = "riscv64")]
+#[path = "../_arch/riscv64/cpu/smp.rs"]
+mod arch_smp;
+
+/// Cross-core calls need IPIs, which only the aarch64 interrupt controllers deliver so far. riscv64
+/// can raise them through the CLINT, but has no trap handling to receive them yet.
+#[cfg(target_arch = "aarch64")]
+mod cross_call;
+
+use crate::bsp;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+pub use arch_smp::core_id;
+
+#[cfg(target_arch = "aarch64")]
+pub use cross_call::{
+    call_on, call_on_all, cross_call_manager, send_reschedule_ipi, stop_other_cores,
+};
+
+#[cfg(target_arch = "aarch64")]
+pub use arch_smp::{secondary_entry_addr, wake_secondary_cores, write_spin_table_entry};
+
+#[cfg(target_arch = "riscv64")]
+pub use arch_smp::{clear_ipi, send_ipi};
+
+/// Online state of every core.
+///
+/// Only plain loads and stores are used, since exclusive accesses need the MMU to be enabled.
+static CORE_ONLINE: [AtomicBool; bsp::cpu::NUM_CORES] =
+    [const { AtomicBool::new(false) }; bsp::cpu::NUM_CORES];
+
+/// Mark the executing core as online.
+pub fn mark_online() {
+    CORE_ONLINE[core_id()].store(true, Ordering::Release);
+}
+
+fn mark_offline() {
+    CORE_ONLINE[core_id()].store(false, Ordering::Release);
+}
+
+pub fn is_online(core_id: usize) -> bool {
+    CORE_ONLINE
+        .get(core_id)
+        .is_some_and(|online| online.load(Ordering::Acquire))
+}
+
+/// Ids of all cores that are online.
+pub fn online_cores() -> impl Iterator<Item = usize> {
+    (0..bsp::cpu::NUM_CORES).filter(|&i| is_online(i))
+}
+
+pub fn num_online_cores() -> usize {
+    online_cores().count()
+}
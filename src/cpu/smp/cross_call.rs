@@ -0,0 +1,229 @@
+//! Cross-core calls, delivered through an IPI.
+
+use super::{core_id, is_online, mark_offline, num_online_cores, online_cores};
+use crate::{
+    cpu::{self, per_cpu::PerCpu},
+    driver,
+    exception::{self, asynchronous::IRQNumber},
+    per_cpu,
+    synchronization::{InitOnce, SpinLock},
+    time,
+};
+use core::{
+    ptr,
+    sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+/// A function that other cores are asked to run.
+struct CallRequest<'a> {
+    f: &'a (dyn Fn() + Sync),
+
+    /// Number of target cores that did not finish running `f` yet.
+    pending: AtomicUsize,
+}
+
+/// Delivers cross-core calls through an IPI.
+pub struct CrossCallManager {
+    irq_number: InitOnce<IRQNumber>,
+}
+
+static CROSS_CALL_MANAGER: CrossCallManager = CrossCallManager {
+    irq_number: InitOnce::new(),
+};
+
+/// The request each core has to run next, if any.
+static CALL_REQUEST: PerCpu<AtomicPtr<CallRequest<'static>>> =
+    per_cpu!(AtomicPtr::new(ptr::null_mut()));
+
+/// Only one cross-core call is in flight at a time.
+static CALL_LOCK: SpinLock<()> = SpinLock::new(());
+
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// How long a panicking core waits for the others to stop.
+const STOP_TIMEOUT: Duration = Duration::from_millis(10);
+
+pub fn cross_call_manager() -> &'static CrossCallManager {
+    &CROSS_CALL_MANAGER
+}
+
+/// Run the request that another core left for the executing core, if any.
+fn handle_call_request() {
+    // Keeps IPIs without a request away from the swap, which needs the MMU to be enabled.
+    if CALL_REQUEST.local().load(Ordering::Relaxed).is_null() {
+        return;
+    }
+
+    let request = CALL_REQUEST.local().swap(ptr::null_mut(), Ordering::Acquire);
+
+    if let Some(request) = unsafe { request.as_ref() } {
+        (request.f)();
+        request.pending.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// Run `f` on all online cores selected by `is_target`, except the executing one, and wait until
+/// all of them finished.
+fn call_on_others(
+    is_target: impl Fn(usize) -> bool,
+    f: &(dyn Fn() + Sync),
+) -> Result<(), &'static str> {
+    let irq_number = CROSS_CALL_MANAGER
+        .irq_number
+        .get()
+        .ok_or("Cross-core calls are not set up yet")?;
+
+    // The request bookkeeping needs atomic read-modify-write operations on all cores involved.
+    if !cpu::exclusives_usable() {
+        return Err("Cross-core calls need the MMU to be enabled");
+    }
+
+    loop {
+        let ret = CALL_LOCK.try_lock(|_| {
+            let request = CallRequest {
+                f,
+                pending: AtomicUsize::new(0),
+            };
+            // The pointer is withdrawn or consumed before `request` goes out of scope.
+            let request_ptr = &request as *const CallRequest as *mut CallRequest<'static>;
+
+            let mut ret = Ok(());
+            let me = core_id();
+            for target in online_cores().filter(|&i| i != me && is_target(i)) {
+                let slot = CALL_REQUEST.get(target).unwrap();
+
+                request.pending.fetch_add(1, Ordering::Relaxed);
+                slot.store(request_ptr, Ordering::Release);
+
+                if let Err(e) = exception::asynchronous::irq_manager().send_ipi(irq_number, target)
+                {
+                    // Withdraw the request, unless the target already picked it up.
+                    if !slot.swap(ptr::null_mut(), Ordering::Relaxed).is_null() {
+                        request.pending.fetch_sub(1, Ordering::Relaxed);
+                    }
+                    ret = Err(e);
+                    break;
+                }
+            }
+
+            while request.pending.load(Ordering::Acquire) != 0 {
+                core::hint::spin_loop();
+            }
+
+            ret
+        });
+
+        match ret {
+            Some(ret) => return ret,
+            // Another core's call is in flight, and this core might be one of its targets.
+            None => handle_call_request(),
+        }
+
+        core::hint::spin_loop();
+    }
+}
+
+/// Run `f` on `target_core` and wait until it finished.
+///
+/// Must not be called from IRQ context.
+pub fn call_on(target_core: usize, f: &(dyn Fn() + Sync)) -> Result<(), &'static str> {
+    if target_core == core_id() {
+        f();
+        return Ok(());
+    }
+
+    if !is_online(target_core) {
+        return Err("Target core is not online");
+    }
+
+    call_on_others(|i| i == target_core, f)
+}
+
+/// Run `f` on all online cores, including the executing one, and wait until all of them finished.
+///
+/// Must not be called from IRQ context.
+pub fn call_on_all(f: &(dyn Fn() + Sync)) -> Result<(), &'static str> {
+    call_on_others(|_| true, f)?;
+    f();
+
+    Ok(())
+}
+
+/// Interrupt `target_core`, so that it reschedules on its way out of the IPI, for example after a
+/// thread of it was woken up.
+pub fn send_reschedule_ipi(target_core: usize) -> Result<(), &'static str> {
+    let irq_number = CROSS_CALL_MANAGER
+        .irq_number
+        .get()
+        .ok_or("Cross-core calls are not set up yet")?;
+
+    exception::asynchronous::irq_manager().send_ipi(irq_number, target_core)
+}
+
+/// Bring all other cores to a halt with IRQs masked, for example before printing a panic.
+///
+/// Gives up waiting after a short time, since cores might have IRQs masked themselves.
+pub fn stop_other_cores() {
+    STOP_REQUESTED.store(true, Ordering::Release);
+
+    let Some(irq_number) = CROSS_CALL_MANAGER.irq_number.get() else {
+        return;
+    };
+
+    let me = core_id();
+    for target in online_cores().filter(|&i| i != me) {
+        let _ = exception::asynchronous::irq_manager().send_ipi(irq_number, target);
+    }
+
+    let deadline = time::time_manager().uptime() + STOP_TIMEOUT;
+    while num_online_cores() > 1 && time::time_manager().uptime() < deadline {}
+}
+
+impl CrossCallManager {
+    pub const COMPATIBLE: &'static str = "Cross-core calls";
+}
+
+impl driver::interface::DeviceDriver for CrossCallManager {
+    type IRQNumberType = IRQNumber;
+
+    fn compatible(&self) -> &'static str {
+        Self::COMPATIBLE
+    }
+
+    unsafe fn init_secondary_core(&self) -> Result<(), &'static str> {
+        if let Some(irq_number) = self.irq_number.get() {
+            exception::asynchronous::irq_manager().enable(irq_number)?;
+        }
+
+        Ok(())
+    }
+
+    fn register_and_enable_irq_handler(
+        &'static self,
+        irq_number: &Self::IRQNumberType,
+    ) -> Result<(), &'static str> {
+        use exception::asynchronous::{irq_manager, IRQHandlerDescriptor};
+
+        let descriptor = IRQHandlerDescriptor::new(*irq_number, Self::COMPATIBLE, self);
+
+        irq_manager().register_handler(descriptor)?;
+        irq_manager().enable(irq_number)?;
+        self.irq_number.set(*irq_number)?;
+
+        Ok(())
+    }
+}
+
+impl exception::asynchronous::interface::IRQHandler for CrossCallManager {
+    fn handle(&self) -> Result<(), &'static str> {
+        if STOP_REQUESTED.load(Ordering::Acquire) {
+            mark_offline();
+            cpu::wait_forever();
+        }
+
+        handle_call_request();
+
+        Ok(())
+    }
+}
@@ -1,37 +1,77 @@
-use crate::info;
-use crate::synchronization::{interface::Mutex, NullLock};
+use crate::{
+    exception, info,
+    synchronization::{interface::ReadWriteEx, RwLock},
+};
+use core::fmt;
 
 const NUM_DRIVERS: usize = 5;
 
-struct DriverManagerInner {
+struct DriverManagerInner<T>
+where
+    T: 'static,
+{
     next_index: usize,
-    descriptors: [Option<DeviceDriverDescriptor>; NUM_DRIVERS],
+    descriptors: [Option<DeviceDriverDescriptor<T>>; NUM_DRIVERS],
 }
 
 pub mod interface {
+    use core::fmt;
+
     pub trait DeviceDriver {
+        /// Different interrupt controllers might use different types for IRQ number.
+        type IRQNumberType: fmt::Display;
+
         fn compatible(&self) -> &'static str;
         unsafe fn init(&self) -> Result<(), &'static str> {
             Ok(())
         }
+
+        /// Called on every secondary core once it runs, for per-core state like banked
+        /// registers.
+        unsafe fn init_secondary_core(&self) -> Result<(), &'static str> {
+            Ok(())
+        }
+
+        /// Called by the kernel to bring up the device's interrupt handling, if the driver's
+        /// descriptor was registered with an IRQ number.
+        fn register_and_enable_irq_handler(
+            &'static self,
+            irq_number: &Self::IRQNumberType,
+        ) -> Result<(), &'static str> {
+            panic!(
+                "Attempt to enable IRQ {} for device {}, but driver does not support this",
+                irq_number,
+                self.compatible()
+            )
+        }
     }
 }
 
 pub type DeviceDriverPostInitCallback = unsafe fn() -> Result<(), &'static str>;
 
 #[derive(Copy, Clone)]
-pub struct DeviceDriverDescriptor {
-    device_driver: &'static (dyn interface::DeviceDriver + Sync),
+pub struct DeviceDriverDescriptor<T>
+where
+    T: 'static,
+{
+    device_driver: &'static (dyn interface::DeviceDriver<IRQNumberType = T> + Sync),
     post_init_callback: Option<DeviceDriverPostInitCallback>,
+    irq_number: Option<T>,
 }
 
-pub struct DriverManager {
-    inner: NullLock<DriverManagerInner>,
+pub struct DriverManager<T>
+where
+    T: 'static,
+{
+    inner: RwLock<DriverManagerInner<T>>,
 }
 
-static DRIVER_MANAGER: DriverManager = DriverManager::new();
+static DRIVER_MANAGER: DriverManager<exception::asynchronous::IRQNumber> = DriverManager::new();
 
-impl DriverManagerInner {
+impl<T> DriverManagerInner<T>
+where
+    T: 'static + Copy,
+{
     pub const fn new() -> Self {
         Self {
             next_index: 0,
@@ -40,38 +80,43 @@ impl DriverManagerInner {
     }
 }
 
-impl DeviceDriverDescriptor {
+impl<T> DeviceDriverDescriptor<T> {
     pub fn new(
-        device_driver: &'static (dyn interface::DeviceDriver + Sync),
+        device_driver: &'static (dyn interface::DeviceDriver<IRQNumberType = T> + Sync),
         post_init_callback: Option<DeviceDriverPostInitCallback>,
+        irq_number: Option<T>,
     ) -> Self {
         Self {
             device_driver,
             post_init_callback,
+            irq_number,
         }
     }
 }
 
-pub fn driver_manager() -> &'static DriverManager {
+pub fn driver_manager() -> &'static DriverManager<exception::asynchronous::IRQNumber> {
     &DRIVER_MANAGER
 }
 
-impl DriverManager {
+impl<T> DriverManager<T>
+where
+    T: fmt::Display + Copy,
+{
     pub const fn new() -> Self {
         Self {
-            inner: NullLock::new(DriverManagerInner::new())
+            inner: RwLock::new(DriverManagerInner::new())
         }
     }
     
-    pub fn register_driver(&self, descriptor: DeviceDriverDescriptor) {
-        self.inner.lock(|inner| {
+    pub fn register_driver(&self, descriptor: DeviceDriverDescriptor<T>) {
+        self.inner.write(|inner| {
             inner.descriptors[inner.next_index] = Some(descriptor);
             inner.next_index += 1;
         });
     }
     
-    fn for_each_descriptor<'a>(&'a self, f: impl FnMut(&'a DeviceDriverDescriptor)) {
-        self.inner.lock(|inner| {
+    fn for_each_descriptor<'a>(&'a self, f: impl FnMut(&'a DeviceDriverDescriptor<T>)) {
+        self.inner.read(|inner| {
             inner
                 .descriptors
                 .iter()
@@ -80,6 +125,11 @@ impl DriverManager {
         })
     }
     
+    /// Initialize all registered drivers, then wire up the IRQ handlers of those that were
+    /// registered with an IRQ number.
+    ///
+    /// IRQ handlers are only registered once every driver finished its init, so that the
+    /// interrupt controller is guaranteed to be available.
     pub unsafe fn init_drivers(&self) {
         self.for_each_descriptor(|d| {
             if let Err(e) = d.device_driver.init() {
@@ -99,8 +149,33 @@ impl DriverManager {
                 }
             }
         });
+
+        self.for_each_descriptor(|d| {
+            if let Some(irq_number) = &d.irq_number {
+                if let Err(e) = d.device_driver.register_and_enable_irq_handler(irq_number) {
+                    panic!(
+                        "Error during driver interrupt handler registration: {}: {}",
+                        d.device_driver.compatible(),
+                        e,
+                    )
+                }
+            }
+        });
     }
     
+    /// Run the per-core init of all drivers on the executing secondary core.
+    pub unsafe fn init_drivers_secondary_core(&self) {
+        self.for_each_descriptor(|d| {
+            if let Err(e) = d.device_driver.init_secondary_core() {
+                panic!(
+                    "Error initializing driver on secondary core: {}: {}",
+                    d.device_driver.compatible(),
+                    e,
+                )
+            }
+        });
+    }
+
     pub fn enumerate(&self) {
         let mut i: usize = 1;
         self.for_each_descriptor(|d| {
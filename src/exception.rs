@@ -0,0 +1,7 @@
+#[cfg(target_arch = "aarch64")]
+#[path = "_arch/aarch64/exception.rs"]
+mod arch_exception;
+
+pub mod asynchronous;
+
+pub use arch_exception::{handling_init, write_faults};
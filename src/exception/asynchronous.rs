@@ -0,0 +1,145 @@
+#[cfg(target_arch = "aarch64")]
+#[path = "../_arch/aarch64/exception/asynchronous.rs"]
+mod arch_asynchronous;
+
+mod null_irq_manager;
+
+use crate::{
+    bsp, cpu::per_cpu::PerCpu, info, per_cpu,
+    synchronization::{self, RwLock},
+};
+use core::{
+    marker::PhantomData,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+pub use arch_asynchronous::print_state;
+
+/// The IRQ number type of the platform's interrupt controller.
+pub type IRQNumber = bsp::exception::asynchronous::IRQNumber;
+
+/// Interrupt descriptor.
+#[derive(Copy, Clone)]
+pub struct IRQHandlerDescriptor<T>
+where
+    T: Copy,
+{
+    number: T,
+    name: &'static str,
+    handler: &'static (dyn interface::IRQHandler + Sync),
+}
+
+/// A token proving that the current code executes in IRQ context.
+///
+/// Only `handle_pending_irqs` of the IRQ manager may be called with it.
+#[derive(Clone, Copy)]
+pub struct IRQContext<'irq_context> {
+    _0: PhantomData<&'irq_context ()>,
+}
+
+pub mod interface {
+    pub trait IRQHandler {
+        /// Called when the corresponding interrupt is asserted.
+        fn handle(&self) -> Result<(), &'static str>;
+    }
+
+    pub trait IRQManager {
+        type IRQNumberType: Copy;
+
+        fn register_handler(
+            &self,
+            irq_handler_descriptor: super::IRQHandlerDescriptor<Self::IRQNumberType>,
+        ) -> Result<(), &'static str>;
+
+        fn enable(&self, irq_number: &Self::IRQNumberType) -> Result<(), &'static str>;
+
+        fn disable(&self, irq_number: &Self::IRQNumberType) -> Result<(), &'static str>;
+
+        /// Raise the inter-processor interrupt `irq_number` on `target_core`.
+        fn send_ipi(
+            &self,
+            irq_number: &Self::IRQNumberType,
+            target_core: usize,
+        ) -> Result<(), &'static str>;
+
+        /// Dispatch all pending IRQs to their registered handlers.
+        fn handle_pending_irqs<'irq_context>(
+            &'irq_context self,
+            ic: &super::IRQContext<'irq_context>,
+        );
+
+        fn print_handler(&self) {}
+    }
+}
+
+impl<T> IRQHandlerDescriptor<T>
+where
+    T: Copy,
+{
+    pub const fn new(
+        number: T,
+        name: &'static str,
+        handler: &'static (dyn interface::IRQHandler + Sync),
+    ) -> Self {
+        Self {
+            number,
+            name,
+            handler,
+        }
+    }
+
+    pub const fn number(&self) -> T {
+        self.number
+    }
+
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub const fn handler(&self) -> &'static (dyn interface::IRQHandler + Sync) {
+        self.handler
+    }
+}
+
+impl IRQContext<'_> {
+    /// # Safety
+    ///
+    /// - Must only be created from the IRQ vector entry.
+    #[inline(always)]
+    pub unsafe fn new() -> Self {
+        IRQContext { _0: PhantomData }
+    }
+}
+
+static CUR_IRQ_MANAGER: RwLock<
+    &'static (dyn interface::IRQManager<IRQNumberType = IRQNumber> + Sync),
+> = RwLock::new(&null_irq_manager::NULL_IRQ_MANAGER);
+
+use synchronization::interface::ReadWriteEx;
+
+pub fn register_irq_manager(
+    new_manager: &'static (dyn interface::IRQManager<IRQNumberType = IRQNumber> + Sync),
+) {
+    CUR_IRQ_MANAGER.write(|manager| *manager = new_manager);
+}
+
+pub fn irq_manager() -> &'static dyn interface::IRQManager<IRQNumberType = IRQNumber> {
+    CUR_IRQ_MANAGER.read(|manager| *manager)
+}
+
+/// Number of IRQ exceptions taken by each core.
+static IRQ_COUNT: PerCpu<AtomicU64> = per_cpu!(AtomicU64::new(0));
+
+/// Account an IRQ exception taken by the executing core.
+pub fn count_irq(_ic: &IRQContext) {
+    // Only the owning core writes its slot, so no read-modify-write atomic is needed.
+    let count = IRQ_COUNT.local();
+    count.store(count.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+}
+
+/// Print the number of IRQ exceptions taken by each core.
+pub fn print_irq_count() {
+    for (core_id, count) in IRQ_COUNT.iter() {
+        info!("      Core {}: {}", core_id, count.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,36 @@
+use super::{interface, IRQContext, IRQHandlerDescriptor, IRQNumber};
+
+pub struct NullIRQManager;
+
+pub static NULL_IRQ_MANAGER: NullIRQManager = NullIRQManager {};
+
+impl interface::IRQManager for NullIRQManager {
+    type IRQNumberType = IRQNumber;
+
+    fn register_handler(
+        &self,
+        _descriptor: IRQHandlerDescriptor<Self::IRQNumberType>,
+    ) -> Result<(), &'static str> {
+        panic!("No IRQ Manager registered yet");
+    }
+
+    fn enable(&self, _irq_number: &Self::IRQNumberType) -> Result<(), &'static str> {
+        Err("No IRQ Manager registered yet")
+    }
+
+    fn disable(&self, _irq_number: &Self::IRQNumberType) -> Result<(), &'static str> {
+        Err("No IRQ Manager registered yet")
+    }
+
+    fn send_ipi(
+        &self,
+        _irq_number: &Self::IRQNumberType,
+        _target_core: usize,
+    ) -> Result<(), &'static str> {
+        Err("No IRQ Manager registered yet")
+    }
+
+    fn handle_pending_irqs<'irq_context>(&'irq_context self, _ic: &IRQContext<'irq_context>) {
+        panic!("No IRQ Manager registered yet");
+    }
+}
@@ -0,0 +1,206 @@
+//! A small executor for `Future`s, without heap allocation.
+//!
+//! Each core runs at most one executor at a time, on the tasks handed to `run`. The executor only
+//! polls tasks that were woken, and blocks the calling thread while none are. Wakers are plain
+//! task indices, so waking is safe from IRQ handlers and from other cores, and a waker that
+//! outlived its executor does no harm.
+//!
+//! IRQ-driven futures keep the wakers of the tasks waiting for them in a `WakerSet`, and wake it
+//! from their handler.
+
+use crate::{
+    cpu::{self, per_cpu::PerCpu},
+    per_cpu,
+    synchronization::{interface::Mutex, IRQSafeSpinLock},
+    thread::{self, ThreadId},
+    workqueue,
+};
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, RawWaker, RawWakerVTable, Waker},
+};
+
+/// Maximum number of tasks of one executor.
+pub const MAX_TASKS: usize = 32;
+
+/// Maximum number of distinct wakers a `WakerSet` holds.
+const MAX_WAKERS: usize = 4;
+
+/// A task, pinned by the caller of `run`.
+pub type Task<'a> = Pin<&'a mut dyn Future<Output = ()>>;
+
+/// Wake-ups of an executor's tasks. Behind a lock rather than atomic, since tasks can be woken
+/// before the MMU is enabled, when atomic read-modify-write operations don't work yet.
+struct WakeState {
+    /// Bit `i` is set when task `i` was woken up.
+    woken: u32,
+
+    /// The thread running the executor, woken up together with its tasks.
+    thread: Option<ThreadId>,
+}
+
+struct CoreExecutor {
+    state: IRQSafeSpinLock<WakeState>,
+
+    /// Only set and cleared by the executor's own core.
+    running: AtomicBool,
+}
+
+/// Wakers of tasks waiting for the same event.
+pub struct WakerSet {
+    wakers: IRQSafeSpinLock<[Option<Waker>; MAX_WAKERS]>,
+}
+
+static EXECUTORS: PerCpu<CoreExecutor> = per_cpu!(CoreExecutor::new());
+
+static WAKER_VTABLE: RawWakerVTable =
+    RawWakerVTable::new(clone_waker, wake_task, wake_task, drop_waker);
+
+impl CoreExecutor {
+    const fn new() -> Self {
+        Self {
+            state: IRQSafeSpinLock::new(WakeState {
+                woken: 0,
+                thread: None,
+            }),
+            running: AtomicBool::new(false),
+        }
+    }
+}
+
+/// The waker data is the core and task index, not a pointer.
+fn raw_waker(core: usize, task: usize) -> RawWaker {
+    RawWaker::new(
+        core::ptr::without_provenance(core * MAX_TASKS + task),
+        &WAKER_VTABLE,
+    )
+}
+
+fn clone_waker(data: *const ()) -> RawWaker {
+    RawWaker::new(data, &WAKER_VTABLE)
+}
+
+fn wake_task(data: *const ()) {
+    let (core, task) = (data.addr() / MAX_TASKS, data.addr() % MAX_TASKS);
+    let Some(executor) = EXECUTORS.get(core) else {
+        return;
+    };
+
+    let thread = executor.state.lock(|state| {
+        state.woken |= 1 << task;
+        state.thread
+    });
+    if let Some(thread) = thread {
+        thread::wake(thread);
+    }
+}
+
+fn drop_waker(_data: *const ()) {}
+
+/// Run `tasks` on the executing core until all of them completed.
+///
+/// Blocks the current thread while no task was woken up. Without a current thread, the core waits
+/// for interrupts instead, so tasks can only be woken up from its own IRQ handlers then.
+pub fn run(tasks: &mut [Task<'_>]) -> Result<(), &'static str> {
+    if tasks.len() > MAX_TASKS {
+        return Err("Too many tasks");
+    }
+
+    let executor = EXECUTORS.local();
+    if executor.running.load(Ordering::Relaxed) {
+        return Err("Executor already running on this core");
+    }
+    executor.running.store(true, Ordering::Relaxed);
+
+    let core = cpu::smp::core_id();
+    let all = ((1u64 << tasks.len()) - 1) as u32;
+    let mut done = 0;
+
+    executor.state.lock(|state| {
+        state.woken = all;
+        state.thread = thread::current();
+    });
+
+    while done != all {
+        let woken = executor.state.lock(|state| core::mem::take(&mut state.woken)) & !done;
+
+        if woken == 0 {
+            if thread::current().is_none() {
+                workqueue::run_idle_work();
+            }
+
+            // IRQs stay masked from the check until blocking, so a wake-up can't get lost.
+            cpu::exec_with_irq_masked(|| {
+                if executor.state.lock(|state| state.woken) & !done != 0 {
+                    return;
+                }
+
+                if thread::current().is_some() {
+                    thread::block();
+                } else if !workqueue::has_idle_work() {
+                    cpu::wait_for_interrupt();
+                }
+            });
+            continue;
+        }
+
+        for (i, task) in tasks.iter_mut().enumerate() {
+            if woken & (1 << i) == 0 {
+                continue;
+            }
+
+            let waker = unsafe { Waker::from_raw(raw_waker(core, i)) };
+            if task.as_mut().poll(&mut Context::from_waker(&waker)).is_ready() {
+                done |= 1 << i;
+            }
+        }
+    }
+
+    executor.state.lock(|state| state.thread = None);
+    executor.running.store(false, Ordering::Relaxed);
+
+    Ok(())
+}
+
+impl WakerSet {
+    pub const fn new() -> Self {
+        Self {
+            wakers: IRQSafeSpinLock::new([const { None }; MAX_WAKERS]),
+        }
+    }
+
+    /// Wake `waker` on the next `wake_all`.
+    pub fn register(&self, waker: &Waker) {
+        let stored = self.wakers.lock(|wakers| {
+            if wakers.iter().flatten().any(|w| w.will_wake(waker)) {
+                return true;
+            }
+
+            match wakers.iter_mut().find(|w| w.is_none()) {
+                Some(free) => {
+                    *free = Some(waker.clone());
+                    true
+                }
+                None => false,
+            }
+        });
+
+        // No room, let the task poll again instead of missing the event.
+        if !stored {
+            waker.wake_by_ref();
+        }
+    }
+
+    /// Wake all registered wakers. Can be called from IRQ handlers.
+    pub fn wake_all(&self) {
+        let wakers = self
+            .wakers
+            .lock(|wakers| core::mem::replace(wakers, [const { None }; MAX_WAKERS]));
+
+        for waker in wakers.into_iter().flatten() {
+            waker.wake();
+        }
+    }
+}
@@ -5,29 +5,86 @@ mod bsp;
 mod console;
 mod cpu;
 mod driver;
+mod exception;
+mod executor;
+mod memory;
 mod panic_wait;
 mod print;
+mod ring_buffer;
 mod synchronization;
+mod thread;
 mod time;
+mod workqueue;
+
+use core::time::Duration;
+
+/// How long a thread runs before another one of its priority gets a turn.
+const TIME_SLICE: Duration = Duration::from_millis(20);
 
 /// Early init code.
 ///
 /// # Safety
 ///
 /// - Only a single core must be active and running this function.
+/// - Entered from the boot code at its virtual address, with the MMU on.
+#[no_mangle]
 unsafe fn kernel_init() -> ! {
+    use memory::mmu::interface::MMU;
+
+    memory::mmu::mmu().disable_identity_map();
+
+    exception::handling_init();
+    cpu::smp::mark_online();
+
     if let Err(e) = bsp::driver::init() {
         panic!("Error initializing BSP driver subsystem: {}", e)
     }
     
     driver::driver_manager().init_drivers();
-    
+
+    bsp::cpu::start_secondary_cores();
+
+    if let Err(e) = thread::init() {
+        panic!("Error starting the scheduler: {}", e)
+    }
+    thread::set_time_slice(TIME_SLICE);
+
+    if let Err(e) = workqueue::start_system_worker() {
+        panic!("Error starting the system work queue: {}", e)
+    }
+
+    // Unmask interrupts on the boot core.
+    cpu::local_irq_unmask();
+
     kernel_main();
 }
 
+/// Early init code of the secondary cores.
+///
+/// # Safety
+///
+/// - Must only be entered once per core, after the boot core released it.
+/// - Entered from the boot code at its virtual address, with the MMU on.
+unsafe fn kernel_init_secondary(core_id: usize) -> ! {
+    use memory::mmu::interface::MMU;
+
+    memory::mmu::mmu().disable_identity_map();
+
+    exception::handling_init();
+    driver::driver_manager().init_drivers_secondary_core();
+    cpu::smp::mark_online();
+
+    cpu::local_irq_unmask();
+
+    secondary_main(core_id)
+}
+
+fn secondary_main(_core_id: usize) -> ! {
+    cpu::wait_forever()
+}
+
 fn kernel_main() -> ! {
     use console::console;
-    use core::time::Duration;
     
     info!(
         "{} version {}",
@@ -36,27 +93,139 @@ fn kernel_main() -> ! {
     );
     info!("Booting on: {}", bsp::board_name());
 
+    let (_, privilege_level) = cpu::current_privilege_level();
+    info!("Current privilege level: {}", privilege_level);
+
+    info!("MMU online. Memory layout:");
+    bsp::memory::mmu::virt_mem_layout().print_layout();
+
+    if let Err(string) = memory::mmu::verify_translation_tables() {
+        panic!("Translation tables: {}", string);
+    }
+    info!("Translation tables match the layout");
+
+    if let Err(string) = memory::mmu::wx_self_test() {
+        panic!("W^X: {}", string);
+    }
+    info!("W^X self-test: write to kernel code faulted");
+
+    info!("Cores online: {}", cpu::smp::num_online_cores());
+    for core_id in cpu::smp::online_cores() {
+        info!("    Core {}", core_id);
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    {
+        info!("Calling into every core:");
+        let hello = || info!("      Hello from core {}", cpu::smp::core_id());
+        if let Err(e) = cpu::smp::call_on_all(&hello) {
+            warn!("Cross-core call failed: {}", e);
+        }
+    }
+
+    info!("Exception handling state:");
+    exception::asynchronous::print_state();
+
     info!("Drivers loaded:");
     driver::driver_manager().enumerate();
+
+    info!("Registered IRQ handlers:");
+    exception::asynchronous::irq_manager().print_handler();
     
     info!("Testing timer");
-    time::time_manager().spin_for(Duration::from_nanos(1));
-    info!("Spinning for 1 second");
-    time::time_manager().spin_for(Duration::from_secs(1));
-    info!("Spinning for 1 second");
-    time::time_manager().spin_for(Duration::from_secs(1));
-    info!("Spinning for 1 second");
-    time::time_manager().spin_for(Duration::from_secs(1));
-    info!("Spinning for 1 second");
-    time::time_manager().spin_for(Duration::from_secs(1));
+    time::sleep(Duration::from_nanos(1));
+
+    if let Err(e) = time::time_manager().set_timeout(Duration::from_millis(1500), || {
+        info!("Timeout fired")
+    }) {
+        panic!("Error setting a timeout: {}", e)
+    }
+    let periodic = match time::time_manager().set_periodic(Duration::from_secs(1), || {
+        info!("Periodic timer fired")
+    }) {
+        Ok(handle) => handle,
+        Err(e) => panic!("Error setting a periodic timer: {}", e),
+    };
+    info!("Sleeping for 1 second");
+    time::sleep(Duration::from_secs(1));
+    info!("Sleeping for 1 second");
+    time::sleep(Duration::from_secs(1));
+    info!("Sleeping for 1 second");
+    time::sleep(Duration::from_secs(1));
+    info!("Sleeping for 1 second");
+    time::sleep(Duration::from_secs(1));
     
+    periodic.cancel();
+
+    info!("IRQs taken per core:");
+    exception::asynchronous::print_irq_count();
     info!("Chars written: {}", console().chars_written());
+    info!("RX overflows: {}", console().rx_overflows());
     
+    let threads = [
+        ("async", thread::Priority::High, async_thread as fn()),
+        ("hello", thread::Priority::Normal, hello_thread),
+        ("busy", thread::Priority::Low, busy_thread),
+    ];
+    for (name, priority, entry) in threads {
+        if let Err(e) = thread::spawn(name, priority, entry) {
+            panic!("Error spawning thread {}: {}", name, e)
+        }
+    }
+
+    info!("Threads:");
+    thread::print_threads();
+
+    thread::exit()
+}
+
+/// Run the async tasks. Blocks while none of them can make progress.
+fn async_thread() {
+    let echo = core::pin::pin!(echo_task());
+    let ticker = core::pin::pin!(ticker_task());
+    let mut tasks: [executor::Task; 2] = [echo, ticker];
+
+    if let Err(e) = executor::run(&mut tasks) {
+        panic!("Error running the async tasks: {}", e)
+    }
+}
+
+/// Echo console input.
+async fn echo_task() {
+    use console::console;
+
     info!("Echoing input now");
 
     console().clear_rx();
     loop {
-        let c = console().read_char();
+        let c = console().read_char_async().await;
         console().write_char(c);
     }
 }
+
+async fn ticker_task() {
+    for i in 1..=3 {
+        time::sleep_async(Duration::from_secs(2)).await;
+        info!("Hello from an async task, round {}", i);
+    }
+}
+
+fn hello_thread() {
+    for i in 1..=3 {
+        info!("Hello from a thread, round {}", i);
+        time::sleep(Duration::from_secs(1));
+    }
+
+    info!("Threads:");
+    thread::print_threads();
+
+    info!("Work queues:");
+    workqueue::system().print_stats();
+}
+
+/// Never yields, the other threads only get to run by preempting it.
+fn busy_thread() {
+    loop {
+        cpu::nop();
+    }
+}
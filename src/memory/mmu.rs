@@ -0,0 +1,255 @@
+//! Memory management unit.
+//!
+//! The kernel runs from the top of the virtual address space, which maps the BSP's physical address
+//! space linearly. The BSP describes its layout as a `KernelVirtualLayout`, and the arch code's
+//! translation tables follow it. The bottom of the virtual address space is left for user address
+//! spaces.
+
+#[cfg(target_arch = "aarch64")]
+#[path = "../_arch/aarch64/memory/mmu.rs"]
+mod arch_mmu;
+
+mod translation_table;
+
+use crate::{exception, info};
+use core::{fmt, ops::RangeInclusive};
+
+pub use arch_mmu::{mmu, verify_translation_tables};
+
+pub mod interface {
+    use super::*;
+
+    pub trait MMU {
+        /// Turn on the MMU and caching of the executing core, using the kernel's translation
+        /// tables at `phys_tables_base_addr`. They were computed at build time, see
+        /// `tools/translation_table_tool`.
+        ///
+        /// Besides the kernel's address space, the tables are also installed as an identity map,
+        /// so the caller can go on at its physical address until it jumped to the kernel's.
+        ///
+        /// # Safety
+        ///
+        /// - Changes the HW's global state.
+        /// - Called by the boot code with the MMU off, so it must not depend on the kernel's link
+        ///   address.
+        unsafe fn enable_mmu_and_caching(
+            &self,
+            phys_tables_base_addr: usize,
+        ) -> Result<(), MMUEnableError>;
+
+        /// Remove the identity map `enable_mmu_and_caching` installed, leaving the bottom of the
+        /// address space unmapped on the executing core.
+        ///
+        /// # Safety
+        ///
+        /// - Nothing may use the identity map anymore.
+        unsafe fn disable_identity_map(&self);
+
+        fn is_enabled(&self) -> bool;
+    }
+}
+
+#[derive(Debug)]
+pub enum MMUEnableError {
+    AlreadyEnabled,
+    Other(&'static str),
+}
+
+/// A translation granule of `GRANULE_SIZE` bytes.
+pub struct TranslationGranule<const GRANULE_SIZE: usize>;
+
+/// An address space of `AS_SIZE` bytes.
+pub struct AddressSpace<const AS_SIZE: usize>;
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum MemAttributes {
+    CacheableDRAM,
+    Device,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum AccessPermissions {
+    ReadOnly,
+    ReadWrite,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub struct AttributeFields {
+    pub mem_attributes: MemAttributes,
+    pub acc_perms: AccessPermissions,
+    pub execute_never: bool,
+}
+
+/// A range bound, either a physical address or the address of a linker symbol.
+#[derive(Copy, Clone)]
+pub enum Bound {
+    Phys(usize),
+    Symbol(&'static str),
+}
+
+/// A named range of the kernel's address space and the attributes it is mapped with. Start and end
+/// must be page aligned.
+pub struct RangeDescriptor {
+    pub name: &'static str,
+    pub start: Bound,
+    pub end_exclusive: Bound,
+    pub attribute_fields: AttributeFields,
+}
+
+/// The kernel's address space from `virt_start` to the top, where `virt_start + x` maps to physical
+/// address `x`. Addresses outside of all ranges stay unmapped. Ranges don't overlap.
+pub struct KernelVirtualLayout<const NUM_RANGES: usize> {
+    virt_start: usize,
+    ranges: [RangeDescriptor; NUM_RANGES],
+
+    /// Resolves the linker symbols of the ranges, which are only known at runtime.
+    symbol_addr: fn(&str) -> Option<usize>,
+}
+
+/// Prints as hex in groups of four digits.
+struct Address(usize);
+
+/// Prints in the largest binary unit that fits without remainder.
+struct Size(usize);
+
+impl fmt::Display for MMUEnableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MMUEnableError::AlreadyEnabled => write!(f, "MMU is already enabled"),
+            MMUEnableError::Other(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+impl<const GRANULE_SIZE: usize> TranslationGranule<GRANULE_SIZE> {
+    pub const SIZE: usize = Self::size_checked();
+    pub const SHIFT: usize = Self::SIZE.trailing_zeros() as usize;
+
+    const fn size_checked() -> usize {
+        assert!(GRANULE_SIZE.is_power_of_two());
+
+        GRANULE_SIZE
+    }
+}
+
+impl<const AS_SIZE: usize> AddressSpace<AS_SIZE> {
+    pub const SIZE: usize = Self::size_checked();
+    pub const SIZE_SHIFT: usize = Self::SIZE.trailing_zeros() as usize;
+
+    const fn size_checked() -> usize {
+        assert!(AS_SIZE.is_power_of_two());
+
+        AS_SIZE
+    }
+}
+
+impl fmt::Display for AttributeFields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let attr = match self.mem_attributes {
+            MemAttributes::CacheableDRAM => "C",
+            MemAttributes::Device => "Dev",
+        };
+
+        let acc_p = match self.acc_perms {
+            AccessPermissions::ReadOnly => "RO",
+            AccessPermissions::ReadWrite => "RW",
+        };
+
+        let xn = if self.execute_never { "XN" } else { "X" };
+
+        write!(f, "{:<3} {} {:<2}", attr, acc_p, xn)
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let groups = (usize::BITS - self.0.leading_zeros()).div_ceil(16).max(2);
+
+        write!(f, "0x")?;
+        for group in (0..groups).rev() {
+            write!(f, "{:04x}", (self.0 >> (group * 16)) & 0xFFFF)?;
+            if group != 0 {
+                write!(f, "_")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [(usize, &str); 3] = [(1 << 30, "GiB"), (1 << 20, "MiB"), (1 << 10, "KiB")];
+
+        // The largest unit the size is a whole multiple of.
+        match UNITS.iter().find(|(unit, _)| self.0.is_multiple_of(*unit)) {
+            Some((unit, name)) => write!(f, "{:>4} {}", self.0 / unit, name),
+            None => write!(f, "{:>4} B", self.0),
+        }
+    }
+}
+
+impl<const NUM_RANGES: usize> KernelVirtualLayout<NUM_RANGES> {
+    pub const fn new(
+        virt_start: usize,
+        ranges: [RangeDescriptor; NUM_RANGES],
+        symbol_addr: fn(&str) -> Option<usize>,
+    ) -> Self {
+        Self {
+            virt_start,
+            ranges,
+            symbol_addr,
+        }
+    }
+
+    fn resolve(&self, bound: Bound) -> usize {
+        match bound {
+            Bound::Phys(addr) => self.virt_start + addr,
+            Bound::Symbol(name) => (self.symbol_addr)(name)
+                .unwrap_or_else(|| panic!("Unknown linker symbol {} in the layout", name)),
+        }
+    }
+
+    fn virtual_range(&self, range: &RangeDescriptor) -> RangeInclusive<usize> {
+        self.resolve(range.start)..=self.resolve(range.end_exclusive) - 1
+    }
+
+    /// The attributes of the page at `virt_addr`, or `None` if it isn't mapped.
+    pub fn virt_addr_properties(&self, virt_addr: usize) -> Option<AttributeFields> {
+        if virt_addr < self.virt_start {
+            return None;
+        }
+
+        self.ranges
+            .iter()
+            .find(|range| self.virtual_range(range).contains(&virt_addr))
+            .map(|range| range.attribute_fields)
+    }
+
+    pub fn print_layout(&self) {
+        for range in self.ranges.iter() {
+            let virtual_range = self.virtual_range(range);
+            let size = virtual_range.end() - virtual_range.start() + 1;
+
+            info!(
+                "      {} - {} | {} | {} | {}",
+                Address(*virtual_range.start()),
+                Address(*virtual_range.end()),
+                Size(size),
+                range.attribute_fields,
+                range.name
+            );
+        }
+    }
+}
+
+/// Check that the kernel's code is write protected, by writing to it.
+pub fn wx_self_test() -> Result<(), &'static str> {
+    let code = wx_self_test as *const () as *mut u8;
+
+    if !unsafe { exception::write_faults(code) } {
+        return Err("Write to kernel code did not fault");
+    }
+
+    Ok(())
+}
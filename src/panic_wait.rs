@@ -22,8 +22,14 @@ fn panic_prevent_reenter() {
 
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    
+    // Keep IRQ handlers from printing into the middle of the panic report.
+    unsafe { cpu::local_irq_mask() };
+
     panic_prevent_reenter();
+
+    // Keep the other cores from printing into the middle of the panic report.
+    #[cfg(target_arch = "aarch64")]
+    cpu::smp::stop_other_cores();
     
     let timestamp = crate::time::time_manager().uptime();
     let (location, line, column) = match info.location() {
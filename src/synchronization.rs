@@ -1,10 +1,28 @@
-use core::cell::UnsafeCell;
+use crate::cpu;
+use core::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicU32, AtomicU8, Ordering},
+};
+
+#[cfg(debug_assertions)]
+use core::{panic::Location, sync::atomic::AtomicUsize};
+
+#[cfg(debug_assertions)]
+mod lockdep;
 
 pub mod interface {
     pub trait Mutex {
         type Data;
         fn lock<'a, R>(&'a self, f: impl FnOnce(&'a mut Self::Data) -> R) -> R;
     }
+
+    /// A lock that gives out either exclusive write access or shared read access.
+    pub trait ReadWriteEx {
+        type Data;
+        fn write<'a, R>(&'a self, f: impl FnOnce(&'a mut Self::Data) -> R) -> R;
+        fn read<'a, R>(&'a self, f: impl FnOnce(&'a Self::Data) -> R) -> R;
+    }
 }
 
 pub struct NullLock<T>
@@ -14,9 +32,80 @@ where
     data: UnsafeCell<T>,
 }
 
+/// A ticket spinlock.
+///
+/// Cores are served in the order they started waiting. The atomic read-modify-write operations
+/// this relies on only work on real hardware once the MMU and data caches are enabled, see
+/// `cpu::exclusives_usable`. Until then, the lock draws no ticket and only hands out the data like
+/// a `NullLock`. This is fine as long as the other cores don't touch locked data before enabling
+/// their MMU either.
+///
+/// In debug builds the lock remembers its owner core and panics on recursive locking instead of
+/// deadlocking silently. Debug builds also validate the order in which locks are taken, see
+/// `lockdep`.
+pub struct SpinLock<T>
+where
+    T: ?Sized,
+{
+    next_ticket: AtomicU32,
+    now_serving: AtomicU32,
+    #[cfg(debug_assertions)]
+    owner: AtomicUsize,
+    #[cfg(debug_assertions)]
+    class: lockdep::LockClass,
+    data: UnsafeCell<T>,
+}
+
+/// A `SpinLock` that additionally masks IRQs on the executing core while the data is accessed.
+///
+/// Use this for data that is also touched from IRQ handlers. Otherwise, a handler interrupting the
+/// lock holder on the same core would spin forever.
+pub struct IRQSafeSpinLock<T>
+where
+    T: ?Sized,
+{
+    inner: SpinLock<T>,
+}
+
 unsafe impl<T> Send for NullLock<T> where T: ?Sized + Send {}
 unsafe impl<T> Sync for NullLock<T> where T: ?Sized + Send {}
 
+/// A reader-writer spinlock for read-mostly data.
+///
+/// Any number of cores may read at the same time, while a writer waits until all readers are
+/// gone. A waiting writer keeps new readers out, so a steady stream of them can't starve it. A
+/// reader must therefore not take the same lock again. IRQs on the executing core are masked while
+/// the data is accessed, so IRQ handlers can take the lock as well.
+///
+/// Like `SpinLock`, the lock only serializes cores once `cpu::exclusives_usable` holds.
+pub struct RwLock<T>
+where
+    T: ?Sized,
+{
+    /// Number of readers, plus the `WRITER` and `WRITER_PENDING` bits.
+    state: AtomicU32,
+    #[cfg(debug_assertions)]
+    class: lockdep::LockClass,
+    data: UnsafeCell<T>,
+}
+
+/// A cell that can be written exactly once, and read from any core afterwards.
+///
+/// Concurrent `set`s from several cores are only caught once `cpu::exclusives_usable` holds.
+pub struct InitOnce<T> {
+    state: AtomicU8,
+    data: UnsafeCell<MaybeUninit<T>>,
+}
+
+unsafe impl<T> Send for SpinLock<T> where T: ?Sized + Send {}
+unsafe impl<T> Sync for SpinLock<T> where T: ?Sized + Send {}
+
+unsafe impl<T> Send for RwLock<T> where T: ?Sized + Send {}
+unsafe impl<T> Sync for RwLock<T> where T: ?Sized + Send + Sync {}
+
+unsafe impl<T> Send for InitOnce<T> where T: Send {}
+unsafe impl<T> Sync for InitOnce<T> where T: Send + Sync {}
+
 impl<T> NullLock<T> {
     pub const fn new(data: T) -> Self {
         Self {
@@ -36,3 +125,338 @@ impl<T> interface::Mutex for NullLock<T> {
     }
     
 }
+
+#[cfg(debug_assertions)]
+const NO_OWNER: usize = usize::MAX;
+
+/// Where a lock is taken, for the lock order validation of debug builds.
+#[cfg(debug_assertions)]
+type LockLocation = &'static Location<'static>;
+
+#[cfg(not(debug_assertions))]
+#[derive(Copy, Clone)]
+struct LockLocation;
+
+#[cfg(debug_assertions)]
+#[track_caller]
+fn lock_location() -> LockLocation {
+    Location::caller()
+}
+
+#[cfg(not(debug_assertions))]
+fn lock_location() -> LockLocation {
+    LockLocation
+}
+
+impl<T> SpinLock<T> {
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub const fn new(data: T) -> Self {
+        Self {
+            next_ticket: AtomicU32::new(0),
+            now_serving: AtomicU32::new(0),
+            #[cfg(debug_assertions)]
+            owner: AtomicUsize::new(NO_OWNER),
+            #[cfg(debug_assertions)]
+            class: lockdep::LockClass::new::<T>(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Like `lock`, but returns `None` instead of waiting if the lock is taken.
+    ///
+    /// Not waiting can't deadlock, so the order isn't checked. Once taken, the lock counts as held
+    /// for the validation of the locks taken inside `f`.
+    pub fn try_lock<'a, R>(&'a self, f: impl FnOnce(&'a mut T) -> R) -> Option<R> {
+        #[cfg(debug_assertions)]
+        self.check_recursion();
+
+        if !cpu::exclusives_usable() {
+            return Some(self.locked(false, f));
+        }
+
+        let ticket = self.now_serving.load(Ordering::Relaxed);
+
+        // Only draw a ticket if it would be served right away.
+        self.next_ticket
+            .compare_exchange(
+                ticket,
+                ticket.wrapping_add(1),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .ok()?;
+
+        Some(self.locked(true, f))
+    }
+
+    #[cfg(debug_assertions)]
+    fn check_recursion(&self) {
+        if self.owner.load(Ordering::Relaxed) == cpu::smp::core_id() {
+            panic!("Recursive locking of a SpinLock");
+        }
+    }
+
+    /// Hand out the data to `f`. The executing core must hold the lock, which is released
+    /// afterwards. Without a `ticket`, there is nothing to release.
+    fn locked<'a, R>(&'a self, ticket: bool, f: impl FnOnce(&'a mut T) -> R) -> R {
+        cpu::preempt::disable();
+
+        #[cfg(debug_assertions)]
+        {
+            self.owner.store(cpu::smp::core_id(), Ordering::Relaxed);
+            lockdep::acquired(&self.class);
+        }
+
+        let data = unsafe { &mut *self.data.get() };
+        let ret = f(data);
+
+        #[cfg(debug_assertions)]
+        {
+            lockdep::released();
+            self.owner.store(NO_OWNER, Ordering::Relaxed);
+        }
+
+        if ticket {
+            // Only the lock holder modifies `now_serving`.
+            let serving = self.now_serving.load(Ordering::Relaxed);
+            self.now_serving
+                .store(serving.wrapping_add(1), Ordering::Release);
+        }
+
+        cpu::preempt::enable();
+
+        ret
+    }
+
+    /// Wait for the lock, taken at `location`, then hand out the data to `f`.
+    #[cfg_attr(not(debug_assertions), allow(unused_variables))]
+    fn lock_at<'a, R>(&'a self, location: LockLocation, f: impl FnOnce(&'a mut T) -> R) -> R {
+        #[cfg(debug_assertions)]
+        {
+            self.check_recursion();
+            lockdep::check_order(&self.class, location);
+        }
+
+        if !cpu::exclusives_usable() {
+            return self.locked(false, f);
+        }
+
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        while self.now_serving.load(Ordering::Acquire) != ticket {
+            core::hint::spin_loop();
+        }
+
+        self.locked(true, f)
+    }
+}
+
+impl<T> interface::Mutex for SpinLock<T> {
+    type Data = T;
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn lock<'a, R>(&'a self, f: impl FnOnce(&'a mut Self::Data) -> R) -> R {
+        self.lock_at(lock_location(), f)
+    }
+}
+
+impl<T> IRQSafeSpinLock<T> {
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub const fn new(data: T) -> Self {
+        Self {
+            inner: SpinLock::new(data),
+        }
+    }
+
+    /// Like `lock`, but returns `None` instead of waiting if the lock is taken.
+    pub fn try_lock<'a, R>(&'a self, f: impl FnOnce(&'a mut T) -> R) -> Option<R> {
+        cpu::exec_with_irq_masked(|| self.inner.try_lock(f))
+    }
+}
+
+impl<T> interface::Mutex for IRQSafeSpinLock<T> {
+    type Data = T;
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn lock<'a, R>(&'a self, f: impl FnOnce(&'a mut Self::Data) -> R) -> R {
+        let location = lock_location();
+
+        // IRQs stay masked until the lock is released, so no handler on this core can try to take
+        // it in between.
+        cpu::exec_with_irq_masked(|| self.inner.lock_at(location, f))
+    }
+}
+
+impl<T> RwLock<T> {
+    const WRITER: u32 = 1 << 31;
+    const WRITER_PENDING: u32 = 1 << 30;
+
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub const fn new(data: T) -> Self {
+        Self {
+            state: AtomicU32::new(0),
+            #[cfg(debug_assertions)]
+            class: lockdep::LockClass::new::<T>(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Validate taking the lock at `location`, and note it as held until `released`.
+    #[cfg_attr(not(debug_assertions), allow(unused_variables))]
+    fn acquiring(&self, location: LockLocation) {
+        #[cfg(debug_assertions)]
+        {
+            lockdep::check_order(&self.class, location);
+            lockdep::acquired(&self.class);
+        }
+    }
+
+    fn released(&self) {
+        #[cfg(debug_assertions)]
+        lockdep::released();
+    }
+}
+
+impl<T> interface::ReadWriteEx for RwLock<T> {
+    type Data = T;
+
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn write<'a, R>(&'a self, f: impl FnOnce(&'a mut Self::Data) -> R) -> R {
+        let location = lock_location();
+
+        cpu::exec_with_irq_masked(|| {
+            self.acquiring(location);
+
+            if !cpu::exclusives_usable() {
+                let ret = f(unsafe { &mut *self.data.get() });
+                self.released();
+                return ret;
+            }
+
+            loop {
+                // Announce the writer, so that no new readers come in while the current ones
+                // leave.
+                let state = self.state.fetch_or(Self::WRITER_PENDING, Ordering::Relaxed);
+
+                if state & !Self::WRITER_PENDING == 0
+                    && self
+                        .state
+                        .compare_exchange_weak(
+                            Self::WRITER_PENDING,
+                            Self::WRITER,
+                            Ordering::Acquire,
+                            Ordering::Relaxed,
+                        )
+                        .is_ok()
+                {
+                    break;
+                }
+
+                core::hint::spin_loop();
+            }
+
+            let ret = f(unsafe { &mut *self.data.get() });
+
+            // Keeps the pending bit of other waiting writers.
+            self.state.fetch_and(!Self::WRITER, Ordering::Release);
+            self.released();
+            ret
+        })
+    }
+
+    /// Readers can wait for a writer too, so their order is validated as well.
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn read<'a, R>(&'a self, f: impl FnOnce(&'a Self::Data) -> R) -> R {
+        let location = lock_location();
+
+        cpu::exec_with_irq_masked(|| {
+            self.acquiring(location);
+
+            if !cpu::exclusives_usable() {
+                let ret = f(unsafe { &*self.data.get() });
+                self.released();
+                return ret;
+            }
+
+            let mut readers = self.state.load(Ordering::Relaxed);
+            loop {
+                if readers & (Self::WRITER | Self::WRITER_PENDING) != 0 {
+                    core::hint::spin_loop();
+                    readers = self.state.load(Ordering::Relaxed);
+                    continue;
+                }
+
+                match self.state.compare_exchange_weak(
+                    readers,
+                    readers + 1,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => break,
+                    Err(x) => readers = x,
+                }
+            }
+
+            let ret = f(unsafe { &*self.data.get() });
+
+            self.state.fetch_sub(1, Ordering::Release);
+            self.released();
+            ret
+        })
+    }
+}
+
+impl<T> InitOnce<T> {
+    const UNINIT: u8 = 0;
+    const INITIALIZING: u8 = 1;
+    const READY: u8 = 2;
+
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(Self::UNINIT),
+            data: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Store `value`. Fails if a value was stored before.
+    pub fn set(&self, value: T) -> Result<(), &'static str> {
+        let claimed = if cpu::exclusives_usable() {
+            self.state
+                .compare_exchange(
+                    Self::UNINIT,
+                    Self::INITIALIZING,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+        } else if self.state.load(Ordering::Relaxed) == Self::UNINIT {
+            self.state.store(Self::INITIALIZING, Ordering::Relaxed);
+            true
+        } else {
+            false
+        };
+
+        if !claimed {
+            return Err("InitOnce already set");
+        }
+
+        unsafe { (*self.data.get()).write(value) };
+        self.state.store(Self::READY, Ordering::Release);
+
+        Ok(())
+    }
+
+    /// The stored value, or `None` if `set` did not complete yet.
+    pub fn get(&self) -> Option<&T> {
+        if self.state.load(Ordering::Acquire) != Self::READY {
+            return None;
+        }
+
+        Some(unsafe { (*self.data.get()).assume_init_ref() })
+    }
+}
+
+impl<T> Drop for InitOnce<T> {
+    fn drop(&mut self) {
+        if *self.state.get_mut() == Self::READY {
+            unsafe { self.data.get_mut().assume_init_drop() };
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! Lock order validation, only compiled into debug builds.
+//!
+//! Every lock belongs to a class, which is the place in the code that created it. Each core keeps
+//! a stack of the classes it currently holds. Taking a lock while holding others records these
+//! orders, and taking two classes in the opposite order of a recorded one prints a warning, since
+//! two cores doing so at the same time would deadlock.
+
+use crate::{
+    cpu::{self, per_cpu::PerCpu},
+    per_cpu, warn,
+};
+use core::{
+    cell::UnsafeCell,
+    panic::Location,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Maximum number of locks a core can hold at the same time and still be tracked.
+const MAX_HELD: usize = 8;
+
+/// Maximum number of distinct lock orders that can be recorded.
+const MAX_ORDERS: usize = 64;
+
+#[derive(Copy, Clone)]
+pub struct LockClass {
+    /// Name of the protected data's type.
+    name: fn() -> &'static str,
+    location: &'static Location<'static>,
+}
+
+/// Lock classes held by one core.
+struct HeldLocks {
+    classes: [Option<LockClass>; MAX_HELD],
+    depth: usize,
+
+    /// Set while a warning is printed, which takes locks itself.
+    reporting: bool,
+}
+
+/// Slot of a `PerCpu<HeldLocks>`.
+///
+/// Only the owning core accesses its slot, and only with IRQs masked.
+struct HeldLocksCell(UnsafeCell<HeldLocks>);
+
+/// `first` was held while `second` was taken.
+#[derive(Copy, Clone)]
+struct LockOrder {
+    first: LockClass,
+    second: LockClass,
+    reported: bool,
+}
+
+struct LockOrders {
+    /// Guards `orders`. Can't be a lock of this module, since those are validated themselves.
+    busy: AtomicBool,
+    orders: UnsafeCell<[Option<LockOrder>; MAX_ORDERS]>,
+}
+
+static HELD_LOCKS: PerCpu<HeldLocksCell> = per_cpu!(HeldLocksCell(UnsafeCell::new(HeldLocks {
+    classes: [None; MAX_HELD],
+    depth: 0,
+    reporting: false,
+})));
+
+static LOCK_ORDERS: LockOrders = LockOrders {
+    busy: AtomicBool::new(false),
+    orders: UnsafeCell::new([None; MAX_ORDERS]),
+};
+
+unsafe impl Sync for HeldLocksCell {}
+unsafe impl Sync for LockOrders {}
+
+impl LockClass {
+    /// Create the class of a lock protecting a `T`, created at the caller's location.
+    #[track_caller]
+    pub const fn new<T: ?Sized>() -> Self {
+        Self {
+            name: core::any::type_name::<T>,
+            location: Location::caller(),
+        }
+    }
+
+    fn is(&self, other: &Self) -> bool {
+        self.location == other.location
+    }
+}
+
+impl LockOrders {
+    /// Record that `held` was held while `taking` was taken. Returns the opposite order, if it
+    /// was recorded before and not reported yet.
+    fn record(&self, held: &LockClass, taking: &LockClass) -> Option<LockOrder> {
+        // Before exclusives are usable, locks don't serialize cores either, see `SpinLock`.
+        while cpu::exclusives_usable()
+            && self
+                .busy
+                .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+        {
+            core::hint::spin_loop();
+        }
+
+        let orders = unsafe { &mut *self.orders.get() };
+        let mut inversion = None;
+        let mut known = false;
+
+        for order in orders.iter_mut().flatten() {
+            if order.first.is(taking) && order.second.is(held) && !order.reported {
+                order.reported = true;
+                inversion = Some(*order);
+            }
+            known |= order.first.is(held) && order.second.is(taking);
+        }
+
+        if !known {
+            if let Some(free) = orders.iter_mut().find(|order| order.is_none()) {
+                *free = Some(LockOrder {
+                    first: *held,
+                    second: *taking,
+                    reported: false,
+                });
+            }
+        }
+
+        self.busy.store(false, Ordering::Release);
+        inversion
+    }
+}
+
+/// Run `f` on the executing core's held locks, unless a warning is being printed.
+fn with_held_locks(f: impl FnOnce(&mut HeldLocks)) {
+    cpu::exec_with_irq_masked(|| {
+        let held = unsafe { &mut *HELD_LOCKS.local().0.get() };
+
+        if !held.reporting {
+            f(held)
+        }
+    })
+}
+
+/// Validate taking a lock of `class` at `location` against the locks held by the executing core.
+pub fn check_order(class: &LockClass, location: &'static Location<'static>) {
+    let mut inversion = None;
+
+    with_held_locks(|held| {
+        for held_class in held.classes[..held.depth.min(MAX_HELD)].iter().flatten() {
+            if !held_class.is(class) {
+                inversion = LOCK_ORDERS.record(held_class, class).or(inversion);
+            }
+        }
+
+        held.reporting = inversion.is_some();
+    });
+
+    if let Some(inversion) = inversion {
+        warn!(
+            "Lock order inversion: taking {} (created at {}) at {} while holding {} (created at \
+             {}), the opposite order was seen before",
+            (class.name)(),
+            class.location,
+            location,
+            (inversion.second.name)(),
+            inversion.second.location,
+        );
+
+        cpu::exec_with_irq_masked(|| unsafe { (*HELD_LOCKS.local().0.get()).reporting = false });
+    }
+}
+
+/// Note that the executing core took a lock of `class`.
+pub fn acquired(class: &LockClass) {
+    with_held_locks(|held| {
+        if held.depth < MAX_HELD {
+            held.classes[held.depth] = Some(*class);
+        }
+        held.depth += 1;
+    });
+}
+
+/// Note that the executing core released the lock it took last.
+pub fn released() {
+    with_held_locks(|held| {
+        held.depth -= 1;
+        if held.depth < MAX_HELD {
+            held.classes[held.depth] = None;
+        }
+    });
+}
@@ -0,0 +1,603 @@
+//! Preemptive kernel threads.
+//!
+//! Threads come from a fixed pool, each with its own statically allocated stack. Threads only run
+//! on the boot core so far, since `init` only turns the boot core into a thread. The scheduler
+//! state is kept per core already, and a thread stays on the core that spawned it. The ready
+//! thread of the highest priority runs, and threads of the same priority take turns whenever one
+//! yields, exits, or used up its time slice. If no thread is ready, the core's idle thread runs.
+//!
+//! There is no periodic tick. A one-shot timer ends the time slice, and it is only armed while
+//! another thread of the current one's priority is ready.
+//!
+//! A thread is never preempted while the core holds a `SpinLock`, see `cpu::preempt`.
+//!
+//! A thread can block until it is woken up again, usually through a `WaitQueue`.
+
+#[cfg(target_arch = "aarch64")]
+#[path = "_arch/aarch64/thread.rs"]
+mod arch_thread;
+
+#[cfg(target_arch = "riscv64")]
+#[path = "_arch/riscv64/thread.rs"]
+mod arch_thread;
+
+mod wait_queue;
+
+pub use wait_queue::WaitQueue;
+
+use crate::{
+    cpu::{self, per_cpu::PerCpu},
+    exception::asynchronous::IRQContext,
+    per_cpu,
+    ring_buffer::RingBuffer,
+    synchronization::{interface::Mutex, IRQSafeSpinLock},
+    time::{time_manager, TimerHandle},
+};
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Maximum number of threads, including the boot core's main thread.
+pub const MAX_THREADS: usize = 8;
+
+const STACK_SIZE: usize = 16 * 1024;
+
+const NUM_PRIORITIES: usize = 4;
+
+const DEFAULT_TIME_SLICE: Duration = Duration::from_millis(50);
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub struct ThreadId(usize);
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum State {
+    Ready,
+    Running,
+    Blocked,
+    Exited,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Only runs when no thread of a higher priority is ready.
+    Idle,
+    Low,
+    Normal,
+    High,
+}
+
+#[derive(Copy, Clone)]
+struct ThreadInfo {
+    name: &'static str,
+    state: State,
+    priority: Priority,
+
+    /// The core the thread runs on.
+    core: usize,
+
+    /// Set if the thread was woken up while it wasn't blocked, so its next `block` returns right
+    /// away.
+    wake_pending: bool,
+
+    /// Time spent running, up to the last switch away from the thread.
+    cpu_time: Duration,
+}
+
+/// The parts of a thread that are accessed without holding a lock.
+///
+/// The context is only touched by the core that switches the thread in or out, and the stack only
+/// by the thread itself.
+struct ThreadSlot {
+    context: UnsafeCell<arch_thread::Context>,
+    stack: UnsafeCell<Stack>,
+}
+
+/// Only accessed through the stack pointer.
+#[repr(align(16))]
+struct Stack {
+    _bytes: [u8; STACK_SIZE],
+}
+
+/// Scheduling state of one core.
+struct CoreScheduler {
+    current: Option<ThreadId>,
+
+    /// Ready threads, one queue per priority.
+    run_queues: [RingBuffer<ThreadId, MAX_THREADS>; NUM_PRIORITIES],
+
+    /// Runs when no other thread is ready. Never queued.
+    idle: Option<ThreadId>,
+
+    /// A thread that exited, but whose stack was still in use until the switch away from it.
+    exited: Option<ThreadId>,
+
+    /// Ends the current thread's time slice. Only armed while another thread of its priority is
+    /// ready.
+    slice_timer: Option<TimerHandle>,
+
+    /// Set when the current thread should be preempted on the next IRQ exit.
+    need_resched: bool,
+
+    /// Uptime when the current thread was switched in.
+    switched_in_at: Duration,
+}
+
+static THREADS: IRQSafeSpinLock<[Option<ThreadInfo>; MAX_THREADS]> =
+    IRQSafeSpinLock::new([None; MAX_THREADS]);
+
+static SLOTS: [ThreadSlot; MAX_THREADS] = [const { ThreadSlot::new() }; MAX_THREADS];
+
+static SCHEDULERS: PerCpu<IRQSafeSpinLock<CoreScheduler>> =
+    per_cpu!(IRQSafeSpinLock::new(CoreScheduler::new()));
+
+/// Time slice, in nanoseconds.
+static TIME_SLICE: AtomicU64 = AtomicU64::new(DEFAULT_TIME_SLICE.as_nanos() as u64);
+
+unsafe impl Sync for ThreadSlot {}
+
+impl fmt::Display for ThreadId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl ThreadSlot {
+    const fn new() -> Self {
+        Self {
+            context: UnsafeCell::new(arch_thread::Context::new()),
+            stack: UnsafeCell::new(Stack {
+                _bytes: [0; STACK_SIZE],
+            }),
+        }
+    }
+
+    fn stack_end_exclusive(&self) -> usize {
+        self.stack.get() as usize + STACK_SIZE
+    }
+}
+
+impl CoreScheduler {
+    const fn new() -> Self {
+        Self {
+            current: None,
+            run_queues: [const { RingBuffer::new() }; NUM_PRIORITIES],
+            idle: None,
+            exited: None,
+            slice_timer: None,
+            need_resched: false,
+            switched_in_at: Duration::ZERO,
+        }
+    }
+
+    fn has_ready(&self) -> bool {
+        self.run_queues.iter().any(|queue| !queue.is_empty())
+    }
+
+    /// Take the next ready thread of the highest priority.
+    fn pop_ready(&mut self) -> Option<ThreadId> {
+        self.run_queues.iter_mut().rev().find_map(|queue| queue.pop())
+    }
+
+    /// Queue the thread `id` of this core as ready.
+    fn make_ready(
+        &mut self,
+        id: ThreadId,
+        threads: &mut [Option<ThreadInfo>; MAX_THREADS],
+    ) -> Result<(), &'static str> {
+        let info = threads[id.0].as_mut().ok_or("No such thread")?;
+        self.run_queues[info.priority as usize]
+            .push(id)
+            .map_err(|_| "Run queue full")?;
+        info.state = State::Ready;
+        let priority = info.priority;
+
+        // Let a thread of higher priority run on the next IRQ.
+        if let Some(current) = self.current.and_then(|current| threads[current.0].as_ref()) {
+            if current.priority < priority {
+                self.need_resched = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Arm the time slice timer if another thread of the current one's priority is ready, so that
+    /// they take turns.
+    ///
+    /// Timers can only be set on the core taking the timer IRQ. That is the boot core, the only
+    /// one running threads so far.
+    fn arm_slice_timer(&mut self, threads: &[Option<ThreadInfo>; MAX_THREADS]) {
+        if self.slice_timer.is_some() {
+            return;
+        }
+
+        let Some(info) = self
+            .current
+            .filter(|&current| Some(current) != self.idle)
+            .and_then(|current| threads[current.0].as_ref())
+        else {
+            return;
+        };
+
+        if !self.run_queues[info.priority as usize].is_empty() {
+            let slice = Duration::from_nanos(TIME_SLICE.load(Ordering::Relaxed));
+
+            // Without the timer, the threads still take turns when one yields or blocks.
+            self.slice_timer = time_manager().set_timeout(slice, slice_expired).ok();
+        }
+    }
+
+    fn cancel_slice_timer(&mut self) {
+        if let Some(timer) = self.slice_timer.take() {
+            timer.cancel();
+        }
+    }
+
+    /// Pick the thread to run after `current`, which is left in `state`, and account the time
+    /// `current` ran. Returns `None` if `current` keeps running.
+    fn switch_from(
+        &mut self,
+        current: ThreadId,
+        state: State,
+        threads: &mut [Option<ThreadInfo>; MAX_THREADS],
+    ) -> Option<ThreadId> {
+        if state == State::Blocked {
+            let info = threads[current.0].as_mut()?;
+            if core::mem::take(&mut info.wake_pending) {
+                return None;
+            }
+        }
+
+        if state == State::Ready && self.idle != Some(current) {
+            let priority = threads[current.0].as_ref()?.priority;
+
+            // There is room, every thread is queued at most once.
+            let _ = self.run_queues[priority as usize].push(current);
+        }
+
+        let next = match self.pop_ready() {
+            Some(next) => next,
+            None => self.idle.filter(|&idle| idle != current)?,
+        };
+        if next == current {
+            return None;
+        }
+
+        let now = time_manager().uptime();
+        if let Some(info) = &mut threads[current.0] {
+            info.cpu_time += now.saturating_sub(self.switched_in_at);
+            info.state = state;
+        }
+        if let Some(info) = &mut threads[next.0] {
+            info.state = State::Running;
+        }
+
+        if state == State::Exited {
+            self.exited = Some(current);
+        }
+        self.current = Some(next);
+        self.switched_in_at = now;
+
+        Some(next)
+    }
+}
+
+/// Take a free slot and prepare it to run `entry` once switched in.
+fn create(name: &'static str, priority: Priority, entry: fn()) -> Result<ThreadId, &'static str> {
+    let id = THREADS.lock(|threads| {
+        let (index, slot) = threads
+            .iter_mut()
+            .enumerate()
+            .find(|(_, info)| info.is_none())
+            .ok_or("No free thread slot")?;
+
+        *slot = Some(ThreadInfo {
+            name,
+            state: State::Ready,
+            priority,
+            core: cpu::smp::core_id(),
+            wake_pending: false,
+            cpu_time: Duration::ZERO,
+        });
+        Ok(ThreadId(index))
+    })?;
+
+    let slot = &SLOTS[id.0];
+    unsafe {
+        *slot.context.get() = arch_thread::Context::new_thread(slot.stack_end_exclusive(), entry)
+    };
+
+    Ok(id)
+}
+
+/// Turn the boot core's flow of execution into the main thread and create its idle thread.
+///
+/// # Safety
+///
+/// - Must only be called once, on the boot core.
+pub unsafe fn init() -> Result<(), &'static str> {
+    THREADS.lock(|threads| {
+        threads[0] = Some(ThreadInfo {
+            name: "main",
+            state: State::Running,
+            priority: Priority::Normal,
+            core: cpu::smp::core_id(),
+            wake_pending: false,
+            cpu_time: Duration::ZERO,
+        })
+    });
+
+    let idle = create("idle", Priority::Idle, idle_thread)?;
+
+    SCHEDULERS.local().lock(|sched| {
+        sched.current = Some(ThreadId(0));
+        sched.idle = Some(idle);
+        sched.switched_in_at = time_manager().uptime();
+    });
+
+    Ok(())
+}
+
+/// Create a thread running `entry` on the executing core, which must have been set up by `init`.
+pub fn spawn(
+    name: &'static str,
+    priority: Priority,
+    entry: fn(),
+) -> Result<ThreadId, &'static str> {
+    let id = create(name, priority, entry)?;
+
+    let queued = SCHEDULERS.local().lock(|sched| {
+        if sched.current.is_none() {
+            return Err("Threads not started on this core");
+        }
+
+        THREADS.lock(|threads| {
+            sched.make_ready(id, threads)?;
+            sched.arm_slice_timer(threads);
+
+            Ok(())
+        })
+    });
+
+    if let Err(e) = queued {
+        THREADS.lock(|threads| threads[id.0] = None);
+        return Err(e);
+    }
+
+    Ok(id)
+}
+
+/// Set how long a thread runs before the next thread of its priority gets a turn. Applies from the
+/// next time slice on.
+pub fn set_time_slice(slice: Duration) {
+    let slice = slice.max(time_manager().resolution());
+    TIME_SLICE.store(slice.as_nanos() as u64, Ordering::Relaxed);
+}
+
+/// End the time slice of the current thread on the next IRQ exit. Runs as the time slice timer.
+fn slice_expired() {
+    SCHEDULERS.local().lock(|sched| {
+        sched.slice_timer = None;
+        sched.need_resched = true;
+    });
+}
+
+/// Preempt the current thread if it used up its time slice, or if it's the idle thread and another
+/// thread became ready.
+///
+/// Called by the arch IRQ handler after all pending IRQs were handled. The switched out thread
+/// resumes from here, on its own stack, and returns from its IRQ.
+pub fn preempt_on_irq_exit(_ic: &IRQContext) {
+    // Sampled before taking the scheduler's own lock.
+    let preemptible = cpu::preempt::is_preemptible();
+
+    let resched = SCHEDULERS.local().lock(|sched| {
+        // Arms the timer for a thread woken up from another core, which can't do so itself. Also
+        // re-arms it if the slice ended while the current thread couldn't be preempted, so that
+        // it is retried.
+        THREADS.lock(|threads| sched.arm_slice_timer(threads));
+
+        if !preemptible {
+            return false;
+        }
+
+        let idle_waiting = sched.current.is_some() && sched.current == sched.idle;
+
+        core::mem::take(&mut sched.need_resched) || (idle_waiting && sched.has_ready())
+    });
+
+    if resched {
+        switch_away(State::Ready);
+    }
+}
+
+/// The thread running on the executing core, if threads were started on it.
+pub fn current() -> Option<ThreadId> {
+    SCHEDULERS.local().lock(|sched| sched.current)
+}
+
+/// Switch to the next ready thread of the executing core, leaving the current one in `state`.
+///
+/// Returns once the current thread is switched back in, or right away if there is no other thread
+/// to switch to.
+fn switch_away(state: State) {
+    debug_assert!(
+        cpu::preempt::is_preemptible(),
+        "Switching threads while holding a SpinLock"
+    );
+
+    cpu::exec_with_irq_masked(|| {
+        let switch = SCHEDULERS.local().lock(|sched| {
+            let current = sched.current?;
+            sched.need_resched = false;
+
+            // The thread that runs next, even if it's `current` again, starts a fresh time slice.
+            sched.cancel_slice_timer();
+
+            THREADS.lock(|threads| {
+                let next = sched.switch_from(current, state, threads);
+                sched.arm_slice_timer(threads);
+
+                next.map(|next| (current, next))
+            })
+        });
+
+        let Some((current, next)) = switch else {
+            return;
+        };
+
+        unsafe {
+            arch_thread::context_switch(
+                SLOTS[current.0].context.get(),
+                SLOTS[next.0].context.get(),
+            )
+        };
+
+        finish_switch();
+    })
+}
+
+/// Called by every thread that was just switched in. Frees the slot of a thread that exited.
+fn finish_switch() {
+    if let Some(id) = SCHEDULERS.local().lock(|sched| sched.exited.take()) {
+        THREADS.lock(|threads| threads[id.0] = None);
+    }
+}
+
+/// Let other threads of the executing core run.
+pub fn yield_now() {
+    switch_away(State::Ready);
+}
+
+/// Block the current thread until `wake` is called for it.
+///
+/// Returns right away if the thread was woken up since it last blocked, or if there is no current
+/// thread. To not miss a wake-up from an IRQ handler of this core, call with IRQs masked, after
+/// making the thread known to the waker.
+pub fn block() {
+    switch_away(State::Blocked);
+}
+
+/// Make the blocked thread `id` ready again. If it isn't blocked, its next `block` returns right
+/// away instead.
+///
+/// Can be called from IRQ handlers and from any core. A thread woken up from another core gets its
+/// core interrupted, so it doesn't wait for the next timer IRQ there.
+pub fn wake(id: ThreadId) {
+    let Some(core) = THREADS.lock(|threads| threads[id.0].map(|info| info.core)) else {
+        return;
+    };
+    let Some(sched) = SCHEDULERS.get(core) else {
+        return;
+    };
+
+    let made_ready = sched.lock(|sched| {
+        THREADS.lock(|threads| {
+            // The slot might have been reused by a thread of another core in between.
+            let Some(info) = threads[id.0].as_mut().filter(|info| info.core == core) else {
+                return false;
+            };
+
+            match info.state {
+                State::Blocked => {
+                    // There is room, every thread is queued at most once.
+                    sched.make_ready(id, threads).is_ok()
+                }
+                State::Ready | State::Running => {
+                    info.wake_pending = true;
+                    false
+                }
+                State::Exited => false,
+            }
+        })
+    });
+
+    #[cfg(target_arch = "aarch64")]
+    if made_ready && core != cpu::smp::core_id() {
+        // Without IPIs, the wake-up is still noticed on the core's next IRQ.
+        let _ = cpu::smp::send_reschedule_ipi(core);
+    }
+}
+
+/// End the current thread.
+pub fn exit() -> ! {
+    switch_away(State::Exited);
+
+    // No other thread is left to switch to. Keep serving IRQs.
+    if let Some(id) = current() {
+        THREADS.lock(|threads| {
+            if let Some(info) = &mut threads[id.0] {
+                info.state = State::Exited;
+            }
+        });
+    }
+    unsafe { cpu::local_irq_unmask() };
+    cpu::wait_forever()
+}
+
+/// The first Rust code of a new thread, reached from `arch_thread`.
+#[no_mangle]
+extern "C" fn thread_start(entry: *const ()) -> ! {
+    // Stored by `spawn` from a `fn()`.
+    let entry: fn() = unsafe { core::mem::transmute(entry) };
+
+    finish_switch();
+
+    // The thread was switched in with IRQs masked, but starts out with them enabled.
+    unsafe { cpu::local_irq_unmask() };
+
+    entry();
+    exit()
+}
+
+/// Runs when no other thread of its core is ready. Switched away from on the IRQ that made one
+/// ready.
+fn idle_thread() {
+    loop {
+        cpu::wait_for_interrupt();
+    }
+}
+
+/// Print all threads with their priority, state and the CPU time they used so far.
+pub fn print_threads() {
+    use crate::info;
+
+    let (current, switched_in_at) =
+        SCHEDULERS.local().lock(|sched| (sched.current, sched.switched_in_at));
+    let mut threads = THREADS.lock(|threads| *threads);
+
+    // The current thread's time is only accounted when it's switched out.
+    if let Some(info) = current.and_then(|id| threads[id.0].as_mut()) {
+        info.cpu_time += time_manager().uptime().saturating_sub(switched_in_at);
+    }
+
+    info!("      ID  NAME         PRIORITY STATE      CPU TIME");
+    for (i, info) in threads.iter().enumerate() {
+        if let Some(info) = info {
+            let priority = match info.priority {
+                Priority::Idle => "Idle",
+                Priority::Low => "Low",
+                Priority::Normal => "Normal",
+                Priority::High => "High",
+            };
+            let state = match info.state {
+                State::Ready => "Ready",
+                State::Running => "Running",
+                State::Blocked => "Blocked",
+                State::Exited => "Exited",
+            };
+            info!(
+                "      {:>2}  {:<12} {:<8} {:<8} {:>4}.{:03}s",
+                i,
+                info.name,
+                priority,
+                state,
+                info.cpu_time.as_secs(),
+                info.cpu_time.subsec_millis()
+            );
+        }
+    }
+}
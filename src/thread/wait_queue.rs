@@ -0,0 +1,118 @@
+//! Threads waiting for an event, for example one signaled by an interrupt handler.
+
+use super::{ThreadId, MAX_THREADS};
+use crate::{
+    cpu,
+    synchronization::{interface::Mutex, IRQSafeSpinLock},
+    workqueue,
+};
+
+/// Waiting threads, oldest first. A thread is in at most once.
+struct Waiters {
+    ids: [Option<ThreadId>; MAX_THREADS],
+}
+
+pub struct WaitQueue {
+    waiters: IRQSafeSpinLock<Waiters>,
+}
+
+impl Waiters {
+    const fn new() -> Self {
+        Self {
+            ids: [None; MAX_THREADS],
+        }
+    }
+
+    fn push(&mut self, id: ThreadId) {
+        if self.ids.contains(&Some(id)) {
+            return;
+        }
+
+        // There is room, there are only `MAX_THREADS` threads.
+        if let Some(free) = self.ids.iter_mut().find(|slot| slot.is_none()) {
+            *free = Some(id);
+        }
+    }
+
+    fn pop(&mut self) -> Option<ThreadId> {
+        let id = self.ids[0].take()?;
+        self.ids.rotate_left(1);
+
+        Some(id)
+    }
+
+    fn remove(&mut self, id: ThreadId) {
+        if let Some(pos) = self.ids.iter().position(|slot| *slot == Some(id)) {
+            self.ids[pos] = None;
+            self.ids[pos..].rotate_left(1);
+        }
+    }
+}
+
+impl WaitQueue {
+    pub const fn new() -> Self {
+        Self {
+            waiters: IRQSafeSpinLock::new(Waiters::new()),
+        }
+    }
+
+    /// Block the current thread until `condition` is true. It is checked again on every wake-up.
+    ///
+    /// `condition` runs with IRQs masked. Without a current thread, for example before threads
+    /// were started on the executing core, this waits for the next interrupt instead of blocking.
+    pub fn wait_until(&self, mut condition: impl FnMut() -> bool) {
+        loop {
+            if super::current().is_none() {
+                workqueue::run_idle_work();
+            }
+
+            // IRQs stay masked from checking the condition until the thread blocked, so a handler
+            // of this core can't wake it up in between.
+            let done = cpu::exec_with_irq_masked(|| {
+                let current = super::current();
+
+                if condition() {
+                    // The thread is still queued if something else than `wake_one` woke it up.
+                    // Left in, it would swallow a later `wake_one` meant for a real waiter.
+                    if let Some(id) = current {
+                        self.waiters.lock(|waiters| waiters.remove(id));
+                    }
+                    return true;
+                }
+
+                match current {
+                    Some(id) => {
+                        self.waiters.lock(|waiters| waiters.push(id));
+                        super::block();
+                    }
+                    // Work queued since it last ran is run first.
+                    None if workqueue::has_idle_work() => (),
+                    // A pending IRQ ends the wait even while masked, and is handled right after.
+                    None => cpu::wait_for_interrupt(),
+                }
+
+                false
+            });
+
+            if done {
+                return;
+            }
+        }
+    }
+
+    /// Wake up the thread that waits the longest. Returns false if there was none.
+    pub fn wake_one(&self) -> bool {
+        match self.waiters.lock(|waiters| waiters.pop()) {
+            Some(id) => {
+                super::wake(id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Wake up all waiting threads.
+    pub fn wake_all(&self) {
+        while self.wake_one() {}
+    }
+}
@@ -1,10 +1,76 @@
+//! Time keeping and timers.
+//!
+//! All timers, including sleeping threads and the scheduler's time slices, wait in one queue sorted
+//! by deadline. The hardware timer is only programmed for the nearest deadline, so it doesn't
+//! interrupt unless some timer expires. There is no periodic tick; whoever needs one sets a
+//! periodic timer.
+
 #[cfg(target_arch = "aarch64")]
 #[path = "_arch/aarch64/time.rs"]
 mod arch_time;
 
-use core::time::Duration;
+#[cfg(target_arch = "riscv64")]
+#[path = "_arch/riscv64/time.rs"]
+mod arch_time;
+
+use crate::{
+    cpu, driver,
+    exception::{self, asynchronous::IRQNumber},
+    synchronization::{interface::Mutex, IRQSafeSpinLock, InitOnce},
+    thread::{self, ThreadId},
+};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+/// Maximum number of pending timers, including sleeping threads.
+const MAX_TIMERS: usize = 16;
+
+/// Called in IRQ context when a timer expires.
+pub type TimerCallback = fn();
+
+/// Identifies a pending timer, to cancel it.
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub struct TimerHandle(u64);
+
+#[derive(Clone)]
+enum TimerAction {
+    Call(TimerCallback),
+    Wake(ThreadId),
+    WakeTask(Waker),
+}
+
+#[derive(Clone)]
+struct Timer {
+    id: u64,
+    deadline: Duration,
+    period: Option<Duration>,
+    action: TimerAction,
+}
+
+/// Pending timers, sorted by deadline.
+struct TimerQueue {
+    timers: [Option<Timer>; MAX_TIMERS],
+    next_id: u64,
+}
 
-pub struct TimeManager;
+/// Returned by `sleep_async`.
+pub struct Sleep {
+    deadline: Duration,
+
+    /// The timer waking up the task, and the task's waker it was set with.
+    timer: Option<(TimerHandle, Waker)>,
+}
+
+pub struct TimeManager {
+    timers: IRQSafeSpinLock<TimerQueue>,
+
+    /// The core taking the timer IRQ. Only its hardware timer is programmed.
+    timer_core: InitOnce<usize>,
+}
 
 static TIME_MANAGER: TimeManager = TimeManager::new();
 
@@ -12,9 +78,142 @@ pub fn time_manager() -> &'static TimeManager {
     &TIME_MANAGER
 }
 
+/// Block the current thread for at least `duration`, see `TimeManager::sleep`.
+pub fn sleep(duration: Duration) {
+    time_manager().sleep(duration)
+}
+
+impl TimerHandle {
+    /// Cancel the timer. Returns false if it already expired, unless it's periodic.
+    pub fn cancel(self) -> bool {
+        time_manager().cancel_timer(self)
+    }
+}
+
+impl TimerQueue {
+    const fn new() -> Self {
+        Self {
+            timers: [const { None }; MAX_TIMERS],
+            next_id: 0,
+        }
+    }
+
+    fn insert(&mut self, timer: Timer) -> Result<(), &'static str> {
+        let len = self.timers.iter().flatten().count();
+        if len == MAX_TIMERS {
+            return Err("Timer queue full");
+        }
+
+        let pos = self.timers[..len]
+            .iter()
+            .flatten()
+            .position(|t| t.deadline > timer.deadline)
+            .unwrap_or(len);
+
+        self.timers[pos..].rotate_right(1);
+        self.timers[pos] = Some(timer);
+
+        Ok(())
+    }
+
+    /// Remove the first timer `matches` returns true for.
+    fn remove(&mut self, matches: impl Fn(&Timer) -> bool) -> bool {
+        let Some(pos) = self.timers.iter().position(|t| t.as_ref().is_some_and(&matches)) else {
+            return false;
+        };
+
+        self.timers[pos] = None;
+        self.timers[pos..].rotate_left(1);
+
+        true
+    }
+
+    /// Take the first timer expired at `now`. A periodic timer is queued again for its next
+    /// period.
+    fn pop_expired(&mut self, now: Duration) -> Option<TimerAction> {
+        let timer = self.timers[0].take_if(|t| t.deadline <= now)?;
+        self.timers.rotate_left(1);
+
+        if let Some(period) = timer.period {
+            // Skip periods that were missed entirely.
+            let mut deadline = timer.deadline + period;
+            if deadline <= now {
+                deadline = now + period;
+            }
+
+            // There is room, the timer was just taken out.
+            let _ = self.insert(Timer {
+                deadline,
+                action: timer.action.clone(),
+                ..timer
+            });
+        }
+
+        Some(timer.action)
+    }
+
+    /// Program the hardware timer for the nearest deadline.
+    fn program(&self) {
+        match &self.timers[0] {
+            Some(timer) => arch_time::set_compare(timer.deadline),
+            None => arch_time::disable_compare(),
+        }
+    }
+}
+
+/// Wait for `duration` without blocking the thread, in an executor task.
+pub fn sleep_async(duration: Duration) -> Sleep {
+    Sleep {
+        deadline: time_manager().uptime() + duration,
+        timer: None,
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if time_manager().uptime() >= self.deadline {
+            return Poll::Ready(());
+        }
+
+        if let Some((handle, waker)) = &self.timer {
+            if waker.will_wake(cx.waker()) {
+                return Poll::Pending;
+            }
+            handle.cancel();
+        }
+
+        let action = TimerAction::WakeTask(cx.waker().clone());
+        match time_manager().add_timer(self.deadline, None, action) {
+            Ok(handle) => self.timer = Some((handle, cx.waker().clone())),
+            Err(_) => {
+                // Can't set a timer on this core, poll again instead.
+                self.timer = None;
+                cx.waker().wake_by_ref();
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some((handle, _)) = &self.timer {
+            handle.cancel();
+        }
+    }
+}
+
 impl TimeManager {
+    pub const COMPATIBLE: &'static str = "ARM Architectural Timer";
+
     pub const fn new() -> Self {
-        Self
+        Self {
+            timers: IRQSafeSpinLock::new(TimerQueue::new()),
+            timer_core: InitOnce::new(),
+        }
     }
     
     pub fn resolution(&self) -> Duration {
@@ -25,7 +224,158 @@ impl TimeManager {
         arch_time::uptime()
     }
     
+    /// Busy-wait for `duration`. Only meant for short delays, or before threads were started;
+    /// use `sleep` otherwise.
     pub fn spin_for(&self, duration:Duration) {
         arch_time::spin_for(duration)
     }
+
+    /// Block the current thread for at least `duration`.
+    ///
+    /// Without a current thread, for example before threads were started on the executing core,
+    /// or if no timer can be set, this spins instead.
+    pub fn sleep(&self, duration: Duration) {
+        let deadline = self.uptime() + duration;
+
+        while self.uptime() < deadline {
+            // IRQs stay masked until the thread blocked, so the timer can't wake it up in between.
+            let slept = cpu::exec_with_irq_masked(|| {
+                let Some(thread) = thread::current() else {
+                    return false;
+                };
+
+                // A thread sleeps at most once, drop a timer left over from a spurious wake-up.
+                self.timers.lock(|queue| {
+                    queue.remove(|t| matches!(t.action, TimerAction::Wake(id) if id == thread))
+                });
+                if self
+                    .add_timer(deadline, None, TimerAction::Wake(thread))
+                    .is_err()
+                {
+                    return false;
+                }
+                thread::block();
+
+                true
+            });
+
+            if !slept {
+                self.spin_for(deadline.saturating_sub(self.uptime()));
+            }
+        }
+    }
+
+    /// Call `callback` once, after `duration`.
+    pub fn set_timeout(
+        &self,
+        duration: Duration,
+        callback: TimerCallback,
+    ) -> Result<TimerHandle, &'static str> {
+        self.add_timer(self.uptime() + duration, None, TimerAction::Call(callback))
+    }
+
+    /// Call `callback` every `period`, until the timer is canceled.
+    pub fn set_periodic(
+        &self,
+        period: Duration,
+        callback: TimerCallback,
+    ) -> Result<TimerHandle, &'static str> {
+        if period < self.resolution() {
+            return Err("Timer period out of range");
+        }
+
+        self.add_timer(self.uptime() + period, Some(period), TimerAction::Call(callback))
+    }
+
+    /// Returns false if the timer is not pending anymore.
+    pub fn cancel_timer(&self, handle: TimerHandle) -> bool {
+        let on_timer_core = self.timer_core.get() == Some(&cpu::smp::core_id());
+
+        self.timers.lock(|queue| {
+            let removed = queue.remove(|t| t.id == handle.0);
+
+            // Elsewhere, the timer IRQ still comes, finds nothing expired and reprograms.
+            if removed && on_timer_core {
+                queue.program();
+            }
+
+            removed
+        })
+    }
+
+    /// Queue a timer and reprogram the hardware timer, if the new one is the nearest.
+    ///
+    /// Only possible on the core taking the timer IRQ, since another core can't program its
+    /// hardware timer.
+    fn add_timer(
+        &self,
+        deadline: Duration,
+        period: Option<Duration>,
+        action: TimerAction,
+    ) -> Result<TimerHandle, &'static str> {
+        match self.timer_core.get() {
+            None => return Err("Timer IRQ not registered"),
+            Some(&core) if core != cpu::smp::core_id() => {
+                return Err("Timers can only be set on the core taking the timer IRQ")
+            }
+            Some(_) => (),
+        }
+
+        self.timers.lock(|queue| {
+            queue.next_id += 1;
+            let id = queue.next_id;
+
+            queue.insert(Timer {
+                id,
+                deadline,
+                period,
+                action,
+            })?;
+            queue.program();
+
+            Ok(TimerHandle(id))
+        })
+    }
+}
+
+impl driver::interface::DeviceDriver for TimeManager {
+    type IRQNumberType = IRQNumber;
+
+    fn compatible(&self) -> &'static str {
+        Self::COMPATIBLE
+    }
+
+    fn register_and_enable_irq_handler(
+        &'static self,
+        irq_number: &Self::IRQNumberType,
+    ) -> Result<(), &'static str> {
+        use exception::asynchronous::{irq_manager, IRQHandlerDescriptor};
+
+        let descriptor = IRQHandlerDescriptor::new(*irq_number, Self::COMPATIBLE, self);
+
+        irq_manager().register_handler(descriptor)?;
+        irq_manager().enable(irq_number)?;
+        self.timer_core.set(cpu::smp::core_id())?;
+
+        Ok(())
+    }
+}
+
+impl exception::asynchronous::interface::IRQHandler for TimeManager {
+    fn handle(&self) -> Result<(), &'static str> {
+        let now = self.uptime();
+
+        // Actions run without the lock, so they can set and cancel timers themselves.
+        while let Some(action) = self.timers.lock(|queue| queue.pop_expired(now)) {
+            match action {
+                TimerAction::Call(callback) => callback(),
+                TimerAction::Wake(thread) => thread::wake(thread),
+                TimerAction::WakeTask(waker) => waker.wake(),
+            }
+        }
+
+        self.timers.lock(|queue| queue.program());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,179 @@
+//! Deferred work, for the bottom halves of IRQ handlers.
+//!
+//! An IRQ handler queues a function plus a context word, and a worker thread runs it later with
+//! IRQs enabled. Before the worker thread was started, queued work runs wherever a core idles
+//! waiting for an event, see `run_idle_work`.
+
+use crate::{
+    info,
+    ring_buffer::RingBuffer,
+    synchronization::{interface::Mutex, IRQSafeSpinLock},
+    thread::{self, WaitQueue},
+    warn,
+};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Runs in thread context, with the context word it was queued with.
+pub type WorkFn = fn(usize);
+
+/// Capacity of the system work queue.
+const SYSTEM_QUEUE_LEN: usize = 32;
+
+struct Work {
+    f: WorkFn,
+    context: usize,
+}
+
+#[derive(Copy, Clone)]
+struct Statistics {
+    queued: usize,
+    run: usize,
+    overruns: usize,
+    max_len: usize,
+}
+
+/// Pending work and the queue's bookkeeping. The statistics are kept under the same lock rather
+/// than in atomics, since work is queued before the MMU is enabled, when atomic read-modify-write
+/// operations don't work yet.
+struct Items<const N: usize> {
+    work: RingBuffer<Work, N>,
+    stats: Statistics,
+
+    /// Set on an overrun, so only the first of a burst is reported. Cleared once the queue ran
+    /// empty.
+    overrun_reported: bool,
+}
+
+/// A queue of at most `N` pending work items.
+pub struct WorkQueue<const N: usize> {
+    name: &'static str,
+    items: IRQSafeSpinLock<Items<N>>,
+    worker: WaitQueue,
+}
+
+static SYSTEM_QUEUE: WorkQueue<SYSTEM_QUEUE_LEN> = WorkQueue::new("system");
+
+static SYSTEM_WORKER_STARTED: AtomicBool = AtomicBool::new(false);
+
+pub fn system() -> &'static WorkQueue<SYSTEM_QUEUE_LEN> {
+    &SYSTEM_QUEUE
+}
+
+impl<const N: usize> Items<N> {
+    const fn new() -> Self {
+        Self {
+            work: RingBuffer::new(),
+            stats: Statistics {
+                queued: 0,
+                run: 0,
+                overruns: 0,
+                max_len: 0,
+            },
+            overrun_reported: false,
+        }
+    }
+}
+
+impl<const N: usize> WorkQueue<N> {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            items: IRQSafeSpinLock::new(Items::new()),
+            worker: WaitQueue::new(),
+        }
+    }
+
+    /// Queue `f` to be called with `context`. Can be called from IRQ handlers.
+    ///
+    /// Fails if the queue is full. The caller then has to do the work right away, or drop it.
+    pub fn queue(&self, f: WorkFn, context: usize) -> Result<(), &'static str> {
+        // On an overrun, whether it's the first of a burst.
+        let queued = self.items.lock(|items| {
+            if items.work.push(Work { f, context }).is_err() {
+                items.stats.overruns += 1;
+                return Err(!core::mem::replace(&mut items.overrun_reported, true));
+            }
+
+            items.stats.queued += 1;
+            items.stats.max_len = items.stats.max_len.max(items.work.len());
+            Ok(())
+        });
+
+        if let Err(first_overrun) = queued {
+            if first_overrun {
+                warn!("Work queue {} overrun, {} items pending", self.name, N);
+            }
+            return Err("Work queue full");
+        }
+
+        self.worker.wake_one();
+
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.items.lock(|items| items.work.is_empty())
+    }
+
+    /// Run all pending work. Returns the number of items run.
+    pub fn run_pending(&self) -> usize {
+        let mut count = 0;
+
+        // Work runs without the lock, so it can queue more work itself.
+        while let Some(work) = self.items.lock(|items| items.work.pop()) {
+            (work.f)(work.context);
+            count += 1;
+        }
+
+        self.items.lock(|items| {
+            items.stats.run += count;
+            items.overrun_reported = false;
+        });
+
+        count
+    }
+
+    /// Run the queue's work forever, blocking while there is none. The body of a worker thread.
+    pub fn run_worker(&self) -> ! {
+        loop {
+            self.worker.wait_until(|| !self.is_empty());
+            self.run_pending();
+        }
+    }
+
+    pub fn print_stats(&self) {
+        let stats = self.items.lock(|items| items.stats);
+
+        info!(
+            "      {:<12} queued {}, run {}, overruns {}, max length {}/{}",
+            self.name, stats.queued, stats.run, stats.overruns, stats.max_len, N
+        );
+    }
+}
+
+fn system_worker() {
+    system().run_worker()
+}
+
+/// Start the thread running the system work queue, on the executing core.
+pub fn start_system_worker() -> Result<(), &'static str> {
+    thread::spawn("kworker", thread::Priority::High, system_worker)?;
+    SYSTEM_WORKER_STARTED.store(true, Ordering::Release);
+
+    Ok(())
+}
+
+/// Whether `run_idle_work` would run anything.
+pub fn has_idle_work() -> bool {
+    !SYSTEM_WORKER_STARTED.load(Ordering::Acquire) && !system().is_empty()
+}
+
+/// Run the system queue's work, unless the worker thread does.
+///
+/// Called where a core waits for an event without a current thread to block, for example before
+/// threads were started.
+pub fn run_idle_work() {
+    if !SYSTEM_WORKER_STARTED.load(Ordering::Acquire) {
+        system().run_pending();
+    }
+}
@@ -0,0 +1,8 @@
+#!/bin/bash
+# Cargo runner of the kernel, see `.cargo/config.toml`: patch the translation tables into the ELF
+# cargo just built, make the image from it and boot it.
+set -x
+export GOOSE_ELF=$1
+source tools/_vars.sh
+tools/strip.sh || exit 1
+tools/qemu.sh
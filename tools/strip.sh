@@ -1,4 +1,5 @@
 #!/bin/bash
 set -x
 source tools/_vars.sh
+tools/translation_tables.sh || exit 1
 rust-objcopy --strip-all -O binary $GOOSE_ELF target/kernel.img
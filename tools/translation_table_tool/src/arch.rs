@@ -0,0 +1,334 @@
+//! AArch64 translation tables for the 64 KiB granule, as laid out by `FixedSizeTranslationTable`
+//! in `src/_arch/aarch64/memory/mmu/translation_table.rs`: all level 3 tables, followed by the
+//! level 2 table.
+//!
+//! The tables translate the offset into a TTBR half, and are installed in both halves. Page n maps
+//! to physical page n, which puts the kernel's address space at the top and the identity map at the
+//! bottom.
+
+use crate::layout::{AccessPermissions, AttributeFields, KernelVirtualLayout, MemAttributes};
+
+pub const PAGE_SHIFT: u64 = 16;
+pub const PAGE_SIZE: u64 = 1 << PAGE_SHIFT;
+
+/// Every level 2 entry covers 512 MiB.
+const LVL2_SHIFT: u64 = 29;
+const NUM_LVL3_ENTRIES: usize = 1 << (LVL2_SHIFT - PAGE_SHIFT);
+const DESCRIPTOR_SIZE: usize = 8;
+
+// Stage 1 descriptor fields, see STAGE1_TABLE_DESCRIPTOR and STAGE1_PAGE_DESCRIPTOR.
+const VALID: u64 = 1 << 0;
+const TYPE_TABLE_OR_PAGE: u64 = 1 << 1;
+const ATTR_INDX_SHIFT: u64 = 2;
+const ATTR_INDX_MASK: u64 = 0b111 << ATTR_INDX_SHIFT;
+const AP_RO_EL1: u64 = 0b10 << 6;
+const AP_MASK: u64 = 0b11 << 6;
+const SH_OUTER_SHAREABLE: u64 = 0b10 << 8;
+const SH_INNER_SHAREABLE: u64 = 0b11 << 8;
+const SH_MASK: u64 = 0b11 << 8;
+const AF: u64 = 1 << 10;
+const ADDR_MASK: u64 = 0xFFFF_FFFF << PAGE_SHIFT;
+const PXN: u64 = 1 << 53;
+const UXN: u64 = 1 << 54;
+
+// MAIR_EL1 indices, see `mair` in `src/_arch/aarch64/memory/mmu.rs`.
+const MAIR_DEVICE: u64 = 0;
+const MAIR_NORMAL: u64 = 1;
+
+/// The kernel's translation tables, for an address space of `num_lvl2_entries` * 512 MiB starting
+/// at `virt_start`.
+pub struct TranslationTables {
+    phys_base: u64,
+    virt_start: u64,
+    num_lvl2_entries: usize,
+}
+
+fn page_descriptor(addr: u64, attributes: AttributeFields) -> u64 {
+    let mut desc = (addr & ADDR_MASK) | AF | TYPE_TABLE_OR_PAGE | VALID | UXN;
+
+    desc |= match attributes.mem_attributes {
+        MemAttributes::CacheableDRAM => SH_INNER_SHAREABLE | (MAIR_NORMAL << ATTR_INDX_SHIFT),
+        MemAttributes::Device => SH_OUTER_SHAREABLE | (MAIR_DEVICE << ATTR_INDX_SHIFT),
+    };
+
+    if attributes.acc_perms == AccessPermissions::ReadOnly {
+        desc |= AP_RO_EL1;
+    }
+
+    if attributes.execute_never {
+        desc |= PXN;
+    }
+
+    desc
+}
+
+/// Decode a valid page descriptor into its output address and attributes.
+fn decode_page_descriptor(desc: u64) -> Result<(u64, AttributeFields), String> {
+    if desc & (AF | TYPE_TABLE_OR_PAGE | UXN) != AF | TYPE_TABLE_OR_PAGE | UXN {
+        return Err(format!("Malformed page descriptor {:#018x}", desc));
+    }
+
+    let mem_attributes = match ((desc & ATTR_INDX_MASK) >> ATTR_INDX_SHIFT, desc & SH_MASK) {
+        (MAIR_NORMAL, SH_INNER_SHAREABLE) => MemAttributes::CacheableDRAM,
+        (MAIR_DEVICE, SH_OUTER_SHAREABLE) => MemAttributes::Device,
+        _ => return Err(format!("Unknown memory attributes in {:#018x}", desc)),
+    };
+
+    let acc_perms = match desc & AP_MASK {
+        AP_RO_EL1 => AccessPermissions::ReadOnly,
+        0 => AccessPermissions::ReadWrite,
+        _ => return Err(format!("EL0 accessible page descriptor {:#018x}", desc)),
+    };
+
+    Ok((
+        desc & ADDR_MASK,
+        AttributeFields {
+            mem_attributes,
+            acc_perms,
+            execute_never: desc & PXN != 0,
+        },
+    ))
+}
+
+fn read_descriptor(bytes: &[u8], index: usize) -> u64 {
+    let offset = index * DESCRIPTOR_SIZE;
+    u64::from_le_bytes(bytes[offset..offset + DESCRIPTOR_SIZE].try_into().unwrap())
+}
+
+impl TranslationTables {
+    /// Tables at physical address `phys_base` covering the layout's address space.
+    pub fn new(phys_base: u64, layout: &KernelVirtualLayout) -> Result<Self, String> {
+        if !phys_base.is_multiple_of(PAGE_SIZE) {
+            return Err(format!("Tables at {:#x} are not page aligned", phys_base));
+        }
+
+        Ok(Self {
+            phys_base,
+            virt_start: layout.virt_start,
+            num_lvl2_entries: (((u64::MAX - layout.virt_start) >> LVL2_SHIFT) + 1) as usize,
+        })
+    }
+
+    /// The size of the kernel's table structure, including the padding to the next page.
+    pub fn size(&self) -> u64 {
+        let size = (self.lvl2_offset() + self.num_lvl2_entries * DESCRIPTOR_SIZE) as u64;
+        size.next_multiple_of(PAGE_SIZE)
+    }
+
+    fn lvl2_offset(&self) -> usize {
+        self.num_lvl2_entries * NUM_LVL3_ENTRIES * DESCRIPTOR_SIZE
+    }
+
+    fn lvl3_table_addr(&self, l2_nr: usize) -> u64 {
+        self.phys_base + (l2_nr * NUM_LVL3_ENTRIES * DESCRIPTOR_SIZE) as u64
+    }
+
+    /// The offset of a page into the address space, which is also its physical address.
+    fn page_offset(l2_nr: usize, l3_nr: usize) -> u64 {
+        ((l2_nr as u64) << LVL2_SHIFT) + ((l3_nr as u64) << PAGE_SHIFT)
+    }
+
+    /// The tables' bytes, mapping every page of the layout.
+    pub fn build(&self, layout: &KernelVirtualLayout) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.size() as usize);
+
+        for l2_nr in 0..self.num_lvl2_entries {
+            for l3_nr in 0..NUM_LVL3_ENTRIES {
+                let phys_addr = Self::page_offset(l2_nr, l3_nr);
+                let desc = layout
+                    .virt_addr_properties(self.virt_start + phys_addr)
+                    .map_or(0, |attributes| page_descriptor(phys_addr, attributes));
+
+                bytes.extend_from_slice(&desc.to_le_bytes());
+            }
+        }
+
+        for l2_nr in 0..self.num_lvl2_entries {
+            let desc = self.lvl3_table_addr(l2_nr) | TYPE_TABLE_OR_PAGE | VALID;
+            bytes.extend_from_slice(&desc.to_le_bytes());
+        }
+
+        bytes.resize(self.size() as usize, 0);
+        bytes
+    }
+
+    /// Decode `bytes` as written by `build`, and check every page against the layout. Returns the
+    /// number of mapped pages.
+    pub fn verify(&self, bytes: &[u8], layout: &KernelVirtualLayout) -> Result<usize, String> {
+        let mut mapped = 0;
+
+        for l2_nr in 0..self.num_lvl2_entries {
+            let desc = read_descriptor(&bytes[self.lvl2_offset()..], l2_nr);
+            if desc != self.lvl3_table_addr(l2_nr) | TYPE_TABLE_OR_PAGE | VALID {
+                return Err(format!("Level 2 entry {} is {:#018x}", l2_nr, desc));
+            }
+
+            for l3_nr in 0..NUM_LVL3_ENTRIES {
+                let phys_addr = Self::page_offset(l2_nr, l3_nr);
+                let virt_addr = self.virt_start + phys_addr;
+                let desc = read_descriptor(bytes, l2_nr * NUM_LVL3_ENTRIES + l3_nr);
+
+                let actual = if desc & VALID != 0 {
+                    let (output_addr, attributes) = decode_page_descriptor(desc)?;
+                    if output_addr != phys_addr {
+                        return Err(format!("Page {:#x} maps to {:#x}", virt_addr, output_addr));
+                    }
+                    Some(attributes)
+                } else {
+                    None
+                };
+
+                let expected = layout.virt_addr_properties(virt_addr);
+                if actual != expected {
+                    return Err(format!(
+                        "Page {:#x} is mapped as {:?}, expected {:?}",
+                        virt_addr, actual, expected
+                    ));
+                }
+
+                mapped += actual.is_some() as usize;
+            }
+        }
+
+        Ok(mapped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::MappedRange;
+
+    const VIRT_START: u64 = 0xFFFF_FFFF_0000_0000;
+
+    fn attribute_fields() -> impl Iterator<Item = AttributeFields> {
+        [MemAttributes::CacheableDRAM, MemAttributes::Device]
+            .into_iter()
+            .flat_map(|mem_attributes| {
+                [AccessPermissions::ReadOnly, AccessPermissions::ReadWrite]
+                    .into_iter()
+                    .flat_map(move |acc_perms| {
+                        [false, true].map(|execute_never| AttributeFields {
+                            mem_attributes,
+                            acc_perms,
+                            execute_never,
+                        })
+                    })
+            })
+    }
+
+    fn layout() -> KernelVirtualLayout {
+        let range = |name, start, end_exclusive, attribute_fields| MappedRange {
+            name,
+            start: VIRT_START + start,
+            end_exclusive: VIRT_START + end_exclusive,
+            attribute_fields,
+        };
+
+        let mut fields = attribute_fields();
+        KernelVirtualLayout {
+            virt_start: VIRT_START,
+            ranges: vec![
+                range("Code", 0x8_0000, 0xA_0000, fields.next().unwrap()),
+                range("Data", 0xA_0000, 0x10_0000, fields.nth(2).unwrap()),
+                // Crosses a level 2 entry.
+                range("MMIO", 0x3FFF_0000, 0x4001_0000, fields.last().unwrap()),
+            ],
+        }
+    }
+
+    #[test]
+    fn page_descriptor_encoding() {
+        let addr = 0x3F20_0000;
+
+        for attributes in attribute_fields() {
+            let desc = page_descriptor(addr, attributes);
+
+            assert_eq!(
+                desc & (VALID | TYPE_TABLE_OR_PAGE | AF | UXN),
+                VALID | TYPE_TABLE_OR_PAGE | AF | UXN
+            );
+            assert_eq!(desc & ADDR_MASK, addr);
+
+            let (attr_indx, sh) = match attributes.mem_attributes {
+                MemAttributes::CacheableDRAM => (MAIR_NORMAL, SH_INNER_SHAREABLE),
+                MemAttributes::Device => (MAIR_DEVICE, SH_OUTER_SHAREABLE),
+            };
+            assert_eq!((desc & ATTR_INDX_MASK) >> ATTR_INDX_SHIFT, attr_indx);
+            assert_eq!(desc & SH_MASK, sh);
+
+            let ap = match attributes.acc_perms {
+                AccessPermissions::ReadOnly => AP_RO_EL1,
+                AccessPermissions::ReadWrite => 0,
+            };
+            assert_eq!(desc & AP_MASK, ap);
+            assert_eq!(desc & PXN != 0, attributes.execute_never);
+
+            assert_eq!(decode_page_descriptor(desc), Ok((addr, attributes)));
+        }
+    }
+
+    #[test]
+    fn malformed_page_descriptors() {
+        let desc = page_descriptor(0, attribute_fields().next().unwrap());
+
+        assert!(decode_page_descriptor(desc & !AF).is_err());
+        assert!(decode_page_descriptor(desc & !UXN).is_err());
+        assert!(decode_page_descriptor(desc & !SH_MASK).is_err());
+        assert!(decode_page_descriptor(desc | (0b01 << 6)).is_err());
+    }
+
+    #[test]
+    fn build_and_verify() {
+        let layout = layout();
+        let tables = TranslationTables::new(0xA_0000, &layout).unwrap();
+
+        // Level 3 tables for 8 * 512 MiB, the level 2 table, and padding to the next page.
+        assert_eq!(tables.size(), 9 * PAGE_SIZE);
+
+        let bytes = tables.build(&layout);
+        assert_eq!(bytes.len() as u64, tables.size());
+        assert_eq!(tables.verify(&bytes, &layout), Ok(2 + 6 + 2));
+
+        // The level 2 entry of the second 512 MiB points to its level 3 table.
+        let lvl2 = read_descriptor(&bytes[tables.lvl2_offset()..], 1);
+        assert_eq!(
+            lvl2,
+            (0xA_0000 + (NUM_LVL3_ENTRIES * DESCRIPTOR_SIZE) as u64) | TYPE_TABLE_OR_PAGE | VALID
+        );
+
+        // The MMIO range's pages on both sides of the level 2 boundary.
+        for (index, addr) in [
+            (2 * NUM_LVL3_ENTRIES - 1, 0x3FFF_0000),
+            (2 * NUM_LVL3_ENTRIES, 0x4000_0000),
+        ] {
+            assert_eq!(
+                decode_page_descriptor(read_descriptor(&bytes, index)),
+                Ok((addr, layout.ranges[2].attribute_fields))
+            );
+        }
+    }
+
+    #[test]
+    fn verify_catches_differences() {
+        let layout = layout();
+        let tables = TranslationTables::new(0xA_0000, &layout).unwrap();
+        let bytes = tables.build(&layout);
+
+        // A page outside of the layout.
+        let mut extra = bytes.clone();
+        extra[0..8]
+            .copy_from_slice(&page_descriptor(0, layout.ranges[0].attribute_fields).to_le_bytes());
+        assert!(tables.verify(&extra, &layout).is_err());
+
+        // Different attributes for a page of the layout.
+        let mut other = layout;
+        other.ranges[0].attribute_fields.execute_never = true;
+        assert!(tables.verify(&bytes, &other).is_err());
+    }
+
+    #[test]
+    fn unaligned_tables() {
+        assert!(TranslationTables::new(0xA_1000, &layout()).is_err());
+    }
+}
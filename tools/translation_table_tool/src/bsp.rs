@@ -0,0 +1,73 @@
+//! The BSPs' layouts, from the kernel's own description, so both always agree.
+
+use crate::elf::Elf;
+use crate::layout::{
+    AccessPermissions, AttributeFields, Bound, LayoutDescriptor, MemAttributes, RangeDescriptor,
+};
+
+#[path = "../../../src/bsp/raspberrypi/memory/layout.rs"]
+mod raspberrypi;
+
+// The tables span from `virt_start` to the top of the virtual address space.
+const _: () = assert!(raspberrypi::KERNEL_VIRT_OFFSET == usize::MAX - raspberrypi::END_INCLUSIVE);
+
+static RPI3: LayoutDescriptor = LayoutDescriptor {
+    virt_start: raspberrypi::KERNEL_VIRT_OFFSET as u64,
+    ranges: &raspberrypi::ranges(
+        raspberrypi::rpi3::MMIO_START,
+        raspberrypi::rpi3::MMIO_END_INCLUSIVE,
+    ),
+};
+
+static RPI4: LayoutDescriptor = LayoutDescriptor {
+    virt_start: raspberrypi::KERNEL_VIRT_OFFSET as u64,
+    ranges: &raspberrypi::ranges(
+        raspberrypi::rpi4::MMIO_START,
+        raspberrypi::rpi4::MMIO_END_INCLUSIVE,
+    ),
+};
+
+/// The layout of the BSP the kernel was built for, as named by its `raspberrypi::BSP_SYMBOL`.
+pub fn layout(kernel: &Elf) -> Result<&'static LayoutDescriptor, String> {
+    let symbol = kernel.symbol(raspberrypi::BSP_SYMBOL)?;
+    let name = kernel.read(symbol.value, symbol.size as usize)?;
+
+    if name == raspberrypi::rpi3::NAME {
+        Ok(&RPI3)
+    } else if name == raspberrypi::rpi4::NAME {
+        Ok(&RPI4)
+    } else {
+        Err(format!(
+            "Unknown BSP {}, expected rpi3 or rpi4",
+            String::from_utf8_lossy(name)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elf::tests::{kernel, LOAD_VADDR};
+
+    fn kernel_for(name: &[u8; 4]) -> Elf {
+        let mut kernel = kernel(&[(raspberrypi::BSP_SYMBOL, LOAD_VADDR, 4)]);
+        kernel.write(LOAD_VADDR, name).unwrap();
+        kernel
+    }
+
+    #[test]
+    fn layout_from_marker() {
+        assert!(std::ptr::eq(layout(&kernel_for(b"rpi3")).unwrap(), &RPI3));
+        assert!(std::ptr::eq(layout(&kernel_for(b"rpi4")).unwrap(), &RPI4));
+    }
+
+    #[test]
+    fn unknown_or_missing_marker() {
+        match layout(&kernel_for(b"rpi5")) {
+            Err(e) => assert!(e.contains("rpi5"), "{}", e),
+            Ok(_) => panic!("rpi5 has no layout"),
+        }
+
+        assert!(layout(&kernel(&[])).is_err());
+    }
+}
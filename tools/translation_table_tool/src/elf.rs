@@ -0,0 +1,309 @@
+//! Just enough of 64-bit little endian ELF to look up symbols and patch loaded data.
+
+use std::ops::Range;
+
+pub const EM_AARCH64: u16 = 183;
+
+const ELF_MAGIC: [u8; 4] = [0x7F, b'E', b'L', b'F'];
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+
+const PT_LOAD: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+
+const PHDR_SIZE: usize = 56;
+const SHDR_SIZE: usize = 64;
+const SYM_SIZE: usize = 24;
+
+#[derive(Copy, Clone)]
+pub struct Symbol {
+    pub value: u64,
+    pub size: u64,
+}
+
+/// The file backed part of a loadable segment.
+struct Segment {
+    vaddr: u64,
+    paddr: u64,
+    offset: u64,
+    filesz: u64,
+}
+
+pub struct Elf {
+    data: Vec<u8>,
+    segments: Vec<Segment>,
+}
+
+fn bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N], String> {
+    data.get(offset..offset + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| format!("ELF truncated at {:#x}", offset))
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Result<u16, String> {
+    Ok(u16::from_le_bytes(bytes(data, offset)?))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32, String> {
+    Ok(u32::from_le_bytes(bytes(data, offset)?))
+}
+
+fn u64_at(data: &[u8], offset: usize) -> Result<u64, String> {
+    Ok(u64::from_le_bytes(bytes(data, offset)?))
+}
+
+fn usize_at(data: &[u8], offset: usize) -> Result<usize, String> {
+    Ok(u64_at(data, offset)? as usize)
+}
+
+impl Elf {
+    pub fn parse(data: Vec<u8>) -> Result<Self, String> {
+        if data.get(0..4) != Some(&ELF_MAGIC[..]) {
+            return Err("Not an ELF file".into());
+        }
+        if data.get(4) != Some(&ELFCLASS64) || data.get(5) != Some(&ELFDATA2LSB) {
+            return Err("Not a 64-bit little endian ELF file".into());
+        }
+
+        let phoff = usize_at(&data, 32)?;
+        let phnum = u16_at(&data, 56)? as usize;
+
+        let mut segments = Vec::new();
+        for i in 0..phnum {
+            let phdr = phoff + i * PHDR_SIZE;
+            if u32_at(&data, phdr)? != PT_LOAD {
+                continue;
+            }
+
+            segments.push(Segment {
+                offset: u64_at(&data, phdr + 8)?,
+                vaddr: u64_at(&data, phdr + 16)?,
+                paddr: u64_at(&data, phdr + 24)?,
+                filesz: u64_at(&data, phdr + 32)?,
+            });
+        }
+
+        Ok(Self { data, segments })
+    }
+
+    pub fn machine(&self) -> u16 {
+        u16::from_le_bytes([self.data[18], self.data[19]])
+    }
+
+    fn section_header(&self, index: usize) -> Result<usize, String> {
+        let shoff = usize_at(&self.data, 40)?;
+        let shnum = u16_at(&self.data, 60)? as usize;
+
+        if index >= shnum {
+            return Err(format!("No section {}", index));
+        }
+
+        Ok(shoff + index * SHDR_SIZE)
+    }
+
+    fn string_at(&self, strtab: usize, offset: usize) -> Result<&[u8], String> {
+        let start = strtab + offset;
+        let len = self.data[start.min(self.data.len())..]
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or("Unterminated string in the string table")?;
+
+        Ok(&self.data[start..start + len])
+    }
+
+    /// Look up a symbol in the symbol tables. The kernel must not be stripped.
+    pub fn symbol(&self, name: &str) -> Result<Symbol, String> {
+        let shnum = u16_at(&self.data, 60)? as usize;
+
+        for i in 0..shnum {
+            let shdr = self.section_header(i)?;
+            if u32_at(&self.data, shdr + 4)? != SHT_SYMTAB {
+                continue;
+            }
+
+            let offset = usize_at(&self.data, shdr + 24)?;
+            let size = usize_at(&self.data, shdr + 32)?;
+            let strtab_shdr = self.section_header(u32_at(&self.data, shdr + 40)? as usize)?;
+            let strtab = usize_at(&self.data, strtab_shdr + 24)?;
+
+            for sym in (offset..offset + size).step_by(SYM_SIZE) {
+                let name_offset = u32_at(&self.data, sym)? as usize;
+                if self.string_at(strtab, name_offset)? == name.as_bytes() {
+                    return Ok(Symbol {
+                        value: u64_at(&self.data, sym + 8)?,
+                        size: u64_at(&self.data, sym + 16)?,
+                    });
+                }
+            }
+        }
+
+        Err(format!("Symbol {} not found", name))
+    }
+
+    /// The segment holding `len` file backed bytes at `vaddr`.
+    fn segment(&self, vaddr: u64, len: usize) -> Result<&Segment, String> {
+        self.segments
+            .iter()
+            .find(|s| vaddr >= s.vaddr && vaddr + len as u64 <= s.vaddr + s.filesz)
+            .ok_or_else(|| {
+                format!(
+                    "{:#x} - {:#x} is not file backed, is it in .bss?",
+                    vaddr,
+                    vaddr + len as u64
+                )
+            })
+    }
+
+    /// The file offsets of `len` bytes loaded at `vaddr`.
+    fn file_range(&self, vaddr: u64, len: usize) -> Result<Range<usize>, String> {
+        let segment = self.segment(vaddr, len)?;
+        let start = (segment.offset + (vaddr - segment.vaddr)) as usize;
+        Ok(start..start + len)
+    }
+
+    /// The load address of `vaddr`.
+    pub fn virt_to_phys(&self, vaddr: u64) -> Result<u64, String> {
+        let segment = self.segment(vaddr, 0)?;
+        Ok(segment.paddr + (vaddr - segment.vaddr))
+    }
+
+    pub fn read(&self, vaddr: u64, len: usize) -> Result<&[u8], String> {
+        let range = self.file_range(vaddr, len)?;
+        Ok(&self.data[range])
+    }
+
+    pub fn write(&mut self, vaddr: u64, bytes: &[u8]) -> Result<(), String> {
+        let range = self.file_range(vaddr, bytes.len())?;
+        self.data[range].copy_from_slice(bytes);
+
+        Ok(())
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub const LOAD_VADDR: u64 = 0xFFFF_FFFF_0008_0000;
+    pub const LOAD_PADDR: u64 = 0x8_0000;
+    pub const LOAD_SIZE: usize = 0x100;
+
+    const SHT_STRTAB: u32 = 3;
+
+    fn put(data: &mut [u8], offset: usize, bytes: &[u8]) {
+        data[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// An AArch64 ELF with `LOAD_SIZE` zeroed bytes loaded at `LOAD_VADDR` and the given
+    /// `(name, value, size)` symbols.
+    pub fn kernel(symbols: &[(&str, u64, u64)]) -> Elf {
+        let phoff = 64;
+        let load_offset = phoff + PHDR_SIZE;
+        let strtab = load_offset + LOAD_SIZE;
+
+        let mut strings = vec![0];
+        let mut name_offsets = Vec::new();
+        for (name, _, _) in symbols {
+            name_offsets.push(strings.len() as u32);
+            strings.extend_from_slice(name.as_bytes());
+            strings.push(0);
+        }
+
+        let symtab = strtab + strings.len().next_multiple_of(8);
+        let symtab_size = (symbols.len() + 1) * SYM_SIZE;
+        let shoff = symtab + symtab_size;
+
+        let mut data = vec![0; shoff + 3 * SHDR_SIZE];
+        put(&mut data, 0, &ELF_MAGIC);
+        put(&mut data, 4, &[ELFCLASS64, ELFDATA2LSB]);
+        put(&mut data, 18, &EM_AARCH64.to_le_bytes());
+        put(&mut data, 32, &(phoff as u64).to_le_bytes());
+        put(&mut data, 40, &(shoff as u64).to_le_bytes());
+        put(&mut data, 56, &1u16.to_le_bytes());
+        put(&mut data, 60, &3u16.to_le_bytes());
+
+        put(&mut data, phoff, &PT_LOAD.to_le_bytes());
+        put(&mut data, phoff + 8, &(load_offset as u64).to_le_bytes());
+        put(&mut data, phoff + 16, &LOAD_VADDR.to_le_bytes());
+        put(&mut data, phoff + 24, &LOAD_PADDR.to_le_bytes());
+        put(&mut data, phoff + 32, &(LOAD_SIZE as u64).to_le_bytes());
+
+        put(&mut data, strtab, &strings);
+
+        // Entry 0 of the symbol table is the null symbol.
+        for (i, (_, value, size)) in symbols.iter().enumerate() {
+            let sym = symtab + (i + 1) * SYM_SIZE;
+            put(&mut data, sym, &name_offsets[i].to_le_bytes());
+            put(&mut data, sym + 8, &value.to_le_bytes());
+            put(&mut data, sym + 16, &size.to_le_bytes());
+        }
+
+        // Section 0 is the null section, 1 the symbol table and 2 its string table.
+        let shdr = shoff + SHDR_SIZE;
+        put(&mut data, shdr + 4, &SHT_SYMTAB.to_le_bytes());
+        put(&mut data, shdr + 24, &(symtab as u64).to_le_bytes());
+        put(&mut data, shdr + 32, &(symtab_size as u64).to_le_bytes());
+        put(&mut data, shdr + 40, &2u32.to_le_bytes());
+
+        let shdr = shoff + 2 * SHDR_SIZE;
+        put(&mut data, shdr + 4, &SHT_STRTAB.to_le_bytes());
+        put(&mut data, shdr + 24, &(strtab as u64).to_le_bytes());
+        put(&mut data, shdr + 32, &(strings.len() as u64).to_le_bytes());
+
+        Elf::parse(data).unwrap()
+    }
+
+    #[test]
+    fn symbol_lookup() {
+        let kernel = kernel(&[
+            ("__code_start", LOAD_VADDR, 0),
+            ("KERNEL_TABLES", LOAD_VADDR + 8, 16),
+        ]);
+
+        let symbol = kernel.symbol("KERNEL_TABLES").unwrap();
+        assert_eq!(symbol.value, LOAD_VADDR + 8);
+        assert_eq!(symbol.size, 16);
+        assert_eq!(kernel.symbol("__code_start").unwrap().value, LOAD_VADDR);
+
+        // A prefix of a symbol's name doesn't match it.
+        assert!(kernel.symbol("__code").is_err());
+    }
+
+    #[test]
+    fn missing_symbol() {
+        let kernel = kernel(&[("__code_start", LOAD_VADDR, 0)]);
+
+        assert_eq!(
+            kernel.symbol("KERNEL_TABLES").err().unwrap(),
+            "Symbol KERNEL_TABLES not found"
+        );
+    }
+
+    #[test]
+    fn rejects_non_elf() {
+        assert!(Elf::parse(b"\x7fELF".to_vec()).is_err());
+        assert!(Elf::parse(vec![0; 64]).is_err());
+    }
+
+    #[test]
+    fn patch_loaded_data() {
+        let mut kernel = kernel(&[]);
+
+        assert_eq!(
+            kernel.virt_to_phys(LOAD_VADDR + 0x10).unwrap(),
+            LOAD_PADDR + 0x10
+        );
+
+        kernel.write(LOAD_VADDR + 0x10, &[1, 2, 3]).unwrap();
+        assert_eq!(kernel.read(LOAD_VADDR + 0xF, 5).unwrap(), &[0, 1, 2, 3, 0]);
+
+        // Beyond the file backed part of the segment.
+        assert!(kernel
+            .write(LOAD_VADDR + LOAD_SIZE as u64 - 1, &[0, 0])
+            .is_err());
+    }
+}
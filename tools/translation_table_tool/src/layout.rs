@@ -0,0 +1,247 @@
+//! The kernel's virtual memory layout, resolved against the symbols of a kernel ELF.
+
+use crate::elf::Elf;
+use std::fmt;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MemAttributes {
+    CacheableDRAM,
+    Device,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AccessPermissions {
+    ReadOnly,
+    ReadWrite,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct AttributeFields {
+    pub mem_attributes: MemAttributes,
+    pub acc_perms: AccessPermissions,
+    pub execute_never: bool,
+}
+
+/// A range boundary, either a physical address or the address of a linker symbol.
+#[derive(Copy, Clone)]
+pub enum Bound {
+    Phys(usize),
+    Symbol(&'static str),
+}
+
+/// A named range of a BSP's layout.
+pub struct RangeDescriptor {
+    pub name: &'static str,
+    pub start: Bound,
+    pub end_exclusive: Bound,
+    pub attribute_fields: AttributeFields,
+}
+
+/// A BSP's layout, as described in the BSP's `memory/layout.rs` of the kernel. The kernel's address
+/// space spans from `virt_start` to the top, where `virt_start + x` maps to physical address `x`.
+pub struct LayoutDescriptor {
+    pub virt_start: u64,
+    pub ranges: &'static [RangeDescriptor],
+}
+
+pub struct MappedRange {
+    pub name: &'static str,
+    pub start: u64,
+    pub end_exclusive: u64,
+    pub attribute_fields: AttributeFields,
+}
+
+pub struct KernelVirtualLayout {
+    pub virt_start: u64,
+    pub ranges: Vec<MappedRange>,
+}
+
+impl Bound {
+    fn resolve(self, kernel: &Elf, virt_start: u64) -> Result<u64, String> {
+        match self {
+            Bound::Phys(addr) => Ok(virt_start + addr as u64),
+            Bound::Symbol(name) => Ok(kernel.symbol(name)?.value),
+        }
+    }
+}
+
+impl LayoutDescriptor {
+    /// Resolve the symbols of the ranges. Ranges must be aligned to `page_size` and must not
+    /// overlap.
+    pub fn resolve(&self, kernel: &Elf, page_size: u64) -> Result<KernelVirtualLayout, String> {
+        let mut ranges: Vec<MappedRange> = Vec::new();
+
+        for desc in self.ranges {
+            let range = MappedRange {
+                name: desc.name,
+                start: desc.start.resolve(kernel, self.virt_start)?,
+                end_exclusive: desc.end_exclusive.resolve(kernel, self.virt_start)?,
+                attribute_fields: desc.attribute_fields,
+            };
+
+            if !range.start.is_multiple_of(page_size)
+                || !range.end_exclusive.is_multiple_of(page_size)
+            {
+                return Err(format!("{} is not page aligned", range.name));
+            }
+            if range.start >= range.end_exclusive || range.start < self.virt_start {
+                return Err(format!("{} is empty or out of bounds", range.name));
+            }
+            if let Some(other) = ranges
+                .iter()
+                .find(|r| range.start < r.end_exclusive && r.start < range.end_exclusive)
+            {
+                return Err(format!("{} overlaps {}", range.name, other.name));
+            }
+
+            ranges.push(range);
+        }
+
+        Ok(KernelVirtualLayout {
+            virt_start: self.virt_start,
+            ranges,
+        })
+    }
+}
+
+impl KernelVirtualLayout {
+    /// The attributes of the page at `virt_addr`, or `None` if it isn't mapped.
+    pub fn virt_addr_properties(&self, virt_addr: u64) -> Option<AttributeFields> {
+        self.ranges
+            .iter()
+            .find(|r| (r.start..r.end_exclusive).contains(&virt_addr))
+            .map(|r| r.attribute_fields)
+    }
+}
+
+impl fmt::Display for AttributeFields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let attr = match self.mem_attributes {
+            MemAttributes::CacheableDRAM => "C",
+            MemAttributes::Device => "Dev",
+        };
+
+        let acc_p = match self.acc_perms {
+            AccessPermissions::ReadOnly => "RO",
+            AccessPermissions::ReadWrite => "RW",
+        };
+
+        let xn = if self.execute_never { "XN" } else { "X" };
+
+        write!(f, "{:<3} {} {:<2}", attr, acc_p, xn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elf::tests::{kernel, LOAD_VADDR};
+
+    const PAGE_SIZE: u64 = 0x1_0000;
+    const VIRT_START: u64 = 0xFFFF_FFFF_0000_0000;
+
+    const RW_XN: AttributeFields = AttributeFields {
+        mem_attributes: MemAttributes::CacheableDRAM,
+        acc_perms: AccessPermissions::ReadWrite,
+        execute_never: true,
+    };
+
+    fn range(name: &'static str, start: Bound, end_exclusive: Bound) -> RangeDescriptor {
+        RangeDescriptor {
+            name,
+            start,
+            end_exclusive,
+            attribute_fields: RW_XN,
+        }
+    }
+
+    fn resolve(ranges: &'static [RangeDescriptor]) -> Result<KernelVirtualLayout, String> {
+        let kernel = kernel(&[
+            ("__code_start", LOAD_VADDR, 0),
+            ("__code_end_exclusive", LOAD_VADDR + PAGE_SIZE, 0),
+            ("__unaligned", LOAD_VADDR + 0x100, 0),
+        ]);
+
+        LayoutDescriptor {
+            virt_start: VIRT_START,
+            ranges,
+        }
+        .resolve(&kernel, PAGE_SIZE)
+    }
+
+    #[test]
+    fn resolves_symbols_and_physical_addresses() {
+        let ranges = Box::leak(Box::new([
+            range(
+                "Code",
+                Bound::Symbol("__code_start"),
+                Bound::Symbol("__code_end_exclusive"),
+            ),
+            range("MMIO", Bound::Phys(0x3F00_0000), Bound::Phys(0x3F01_0000)),
+        ]));
+        let layout = resolve(ranges).unwrap();
+
+        assert_eq!(layout.ranges[0].start, LOAD_VADDR);
+        assert_eq!(layout.ranges[0].end_exclusive, LOAD_VADDR + PAGE_SIZE);
+        assert_eq!(layout.ranges[1].start, VIRT_START + 0x3F00_0000);
+
+        assert_eq!(
+            layout.virt_addr_properties(LOAD_VADDR + PAGE_SIZE - 1),
+            Some(RW_XN)
+        );
+        assert_eq!(layout.virt_addr_properties(LOAD_VADDR + PAGE_SIZE), None);
+    }
+
+    #[test]
+    fn rejects_unaligned_ranges() {
+        let ranges = Box::leak(Box::new([range(
+            "Code",
+            Bound::Symbol("__code_start"),
+            Bound::Symbol("__unaligned"),
+        )]));
+        assert_eq!(resolve(ranges).err().unwrap(), "Code is not page aligned");
+
+        let ranges = Box::leak(Box::new([range(
+            "MMIO",
+            Bound::Phys(0x3F00_1000),
+            Bound::Phys(0x3F01_0000),
+        )]));
+        assert_eq!(resolve(ranges).err().unwrap(), "MMIO is not page aligned");
+    }
+
+    #[test]
+    fn rejects_empty_and_overlapping_ranges() {
+        let ranges = Box::leak(Box::new([range(
+            "Code",
+            Bound::Symbol("__code_end_exclusive"),
+            Bound::Symbol("__code_start"),
+        )]));
+        assert_eq!(
+            resolve(ranges).err().unwrap(),
+            "Code is empty or out of bounds"
+        );
+
+        let ranges = Box::leak(Box::new([
+            range(
+                "Code",
+                Bound::Symbol("__code_start"),
+                Bound::Symbol("__code_end_exclusive"),
+            ),
+            range("Stack", Bound::Phys(0), Bound::Phys(0x9_0000)),
+        ]));
+        assert_eq!(resolve(ranges).err().unwrap(), "Stack overlaps Code");
+    }
+
+    #[test]
+    fn rejects_missing_symbols() {
+        let ranges = Box::leak(Box::new([range(
+            "Data",
+            Bound::Symbol("__data_start"),
+            Bound::Symbol("__data_end_exclusive"),
+        )]));
+        assert_eq!(
+            resolve(ranges).err().unwrap(),
+            "Symbol __data_start not found"
+        );
+    }
+}
@@ -0,0 +1,85 @@
+//! Precompute the kernel's translation tables.
+//!
+//! Reads the linker symbols of a kernel ELF, computes the final translation tables for the layout
+//! of the BSP the kernel names and writes them into the `.data` resident `KERNEL_TABLES`, so no
+//! table construction runs at boot. The written tables are read back and checked against the
+//! layout before the ELF is saved.
+//!
+//! The boot code installs the tables at their load address, which must be where the layout maps
+//! their link address.
+//!
+//! Usage: translation_table_tool <kernel ELF>
+
+mod arch;
+mod bsp;
+mod elf;
+mod layout;
+
+use std::{env, fs, process};
+
+const TABLES_SYMBOL: &str = "KERNEL_TABLES";
+
+fn run(path: &str) -> Result<(), String> {
+    let data = fs::read(path).map_err(|e| format!("Reading {}: {}", path, e))?;
+    let mut kernel = elf::Elf::parse(data)?;
+    if kernel.machine() != elf::EM_AARCH64 {
+        return Err(format!("{} is not an AArch64 ELF", path));
+    }
+
+    let layout = bsp::layout(&kernel)?.resolve(&kernel, arch::PAGE_SIZE)?;
+    for range in &layout.ranges {
+        println!(
+            "      {:#018x} - {:#018x} | {} | {}",
+            range.start,
+            range.end_exclusive - 1,
+            range.attribute_fields,
+            range.name
+        );
+    }
+
+    let symbol = kernel.symbol(TABLES_SYMBOL)?;
+    let phys_addr = kernel.virt_to_phys(symbol.value)?;
+    if symbol.value.checked_sub(layout.virt_start) != Some(phys_addr) {
+        return Err(format!(
+            "{} is linked at {:#x} but loaded at {:#x}. Does the link offset match the layout?",
+            TABLES_SYMBOL, symbol.value, phys_addr
+        ));
+    }
+
+    let tables = arch::TranslationTables::new(phys_addr, &layout)?;
+    if symbol.size != tables.size() {
+        return Err(format!(
+            "{} is {} bytes, expected {}. Does the layout's address space match the kernel's?",
+            TABLES_SYMBOL,
+            symbol.size,
+            tables.size()
+        ));
+    }
+
+    kernel.write(symbol.value, &tables.build(&layout))?;
+
+    let written = kernel.read(symbol.value, tables.size() as usize)?;
+    let mapped = tables.verify(written, &layout)?;
+
+    fs::write(path, kernel.into_bytes()).map_err(|e| format!("Writing {}: {}", path, e))?;
+
+    println!(
+        "      {} at {:#x} (physical {:#x}): {} pages mapped, verified against the layout",
+        TABLES_SYMBOL, symbol.value, phys_addr, mapped
+    );
+
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 2 {
+        eprintln!("Usage: {} <kernel ELF>", args[0]);
+        process::exit(2);
+    }
+
+    if let Err(e) = run(&args[1]) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,5 @@
+#!/bin/bash
+set -x
+source tools/_vars.sh
+HOST=$(rustc -vV | sed -n 's/^host: //p')
+cargo run --quiet -p translation_table_tool --target $HOST -- $GOOSE_ELF